@@ -0,0 +1,319 @@
+// command line lookups, build with `--features cli`:
+//
+//     pagerank example.com https://www.rust-lang.org/
+//     pagerank --file domains.txt --output csv > ranks.csv
+//     cat domains.txt | pagerank --output ndjson
+//
+// the key is read from --key, OPENPAGERANK_API_KEY or the `api_key` line of the config file
+
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+use clap::{Parser, ValueEnum};
+
+use pagerank::blocking::PageRankClient;
+use pagerank::{Error, NormalizeOptions, PageRank, Response, Result, RetryPolicy};
+
+const KEY_ENV: &str = "OPENPAGERANK_API_KEY";
+const API_ROOT_ENV: &str = "OPENPAGERANK_API_ROOT";
+
+#[derive(Debug, Parser)]
+#[command(name = "pagerank", version, about = "Look up Open PageRank scores of domains and urls")]
+struct Args {
+    #[arg(help = "Domains or urls, read from --file or stdin when none are given")]
+    domains: Vec<String>,
+
+    #[arg(short, long, help = "File with one domain per line, - for stdin")]
+    file: Option<PathBuf>,
+
+    #[arg(short, long, env = KEY_ENV, hide_env_values = true, help = "Open PageRank api key")]
+    key: Option<String>,
+
+    #[arg(long, help = "Config file, defaults to ~/.config/pagerank/config.toml")]
+    config: Option<PathBuf>,
+
+    #[arg(short, long, value_enum, default_value_t = Output::Table)]
+    output: Output,
+
+    #[arg(long, help = "Rank www.example.com as example.com")]
+    strip_www: bool,
+
+    #[arg(long, default_value_t = 10, help = "Seconds per request")]
+    timeout: u64,
+
+    #[arg(long, default_value_t = 4, help = "Requests of 100 domains in flight")]
+    concurrency: usize,
+
+    #[arg(long, default_value_t = 3, help = "Attempts per request on rate limits and server errors")]
+    retries: u32,
+
+    #[arg(long, help = "Api root, defaults to OPENPAGERANK_API_ROOT or the public api")]
+    base_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Output {
+    Table,
+    Json,
+    Ndjson,
+    Csv,
+}
+
+// `name = "value"` lines with # comments, the toml subset the cli needs
+#[derive(Debug, Default, PartialEq)]
+struct Config {
+    api_key: Option<String>,
+    base_url: Option<String>,
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("pagerank: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Args) -> Result<()> {
+    let config = match args.config.clone().or_else(config_path) {
+        Some(path) if args.config.is_some() || path.exists() => Config::parse(&fs::read_to_string(path)?)?,
+        _ => Config::default(),
+    };
+    let key = args.key.clone()
+        .or(config.api_key)
+        .ok_or_else(|| Error::Config(format!("no api key, pass --key or set {}", KEY_ENV)))?;
+
+    let mut builder = PageRankClient::builder(&key)
+        .timeout(Duration::from_secs(args.timeout))
+        .concurrency(args.concurrency)
+        .retry(RetryPolicy::new().max_attempts(args.retries))
+        .normalize(NormalizeOptions::new().strip_www(args.strip_www));
+    // the config file comes last, after OPENPAGERANK_API_ROOT which the builder reads itself
+    let base_url = args.base_url.clone().or(config.base_url.filter(|_| env::var_os(API_ROOT_ENV).is_none()));
+    if let Some(base_url) = base_url {
+        builder = builder.base_url(&base_url);
+    }
+
+    let domains = domains(&args)?;
+    if domains.is_empty() {
+        return Err(Error::Config("no domains given".to_string()))
+    }
+    let rank = builder.build()?.rank_all(&domains)?;
+
+    let mut out = io::stdout().lock();
+    write(&mut out, &rank, args.output)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn config_path() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(dir.join("pagerank").join("config.toml"))
+}
+
+impl Config {
+    fn parse(content: &str) -> Result<Self> {
+        let mut config = Config::default();
+        for (n, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            let (name, value) = line.split_once('=')
+                .ok_or_else(|| Error::Config(format!("config line {}: expected name = value", n + 1)))?;
+            let value = toml_string(value)
+                .ok_or_else(|| Error::Config(format!("config line {}: expected a quoted string", n + 1)))?;
+            match name.trim() {
+                "api_key" => config.api_key = Some(value),
+                "base_url" => config.base_url = Some(value),
+                _ => {}
+            }
+        }
+        Ok(config)
+    }
+}
+
+// a toml string: "basic" with \\ and \" escapes or 'literal', followed by nothing but a comment
+fn toml_string(raw: &str) -> Option<String> {
+    let raw = raw.trim();
+    let quote = raw.chars().next().filter(|x| matches!(x, '"' | '\''))?;
+
+    let mut value = String::new();
+    let mut chars = raw[1..].char_indices();
+    let end = loop {
+        match chars.next()? {
+            (i, c) if c == quote => break i + 2,
+            (_, '\\') if quote == '"' => match chars.next()?.1 {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                c @ ('"' | '\\') => value.push(c),
+                _ => return None,
+            },
+            (_, c) => value.push(c),
+        }
+    };
+
+    let rest = raw[end..].trim_start();
+    (rest.is_empty() || rest.starts_with('#')).then_some(value)
+}
+
+// arguments first, then the file, stdin only when neither is given
+fn domains(args: &Args) -> Result<Vec<String>> {
+    let mut domains = args.domains.clone();
+    let lines = match &args.file {
+        Some(path) if path == Path::new("-") => read_lines(io::stdin().lock())?,
+        Some(path) => read_lines(io::BufReader::new(fs::File::open(path)?))?,
+        None if domains.is_empty() => read_lines(io::stdin().lock())?,
+        None => Vec::new(),
+    };
+    domains.extend(lines);
+    Ok(domains)
+}
+
+// blank lines and # comments are skipped
+fn read_lines<R: BufRead>(reader: R) -> io::Result<Vec<String>> {
+    let mut lines = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() && !line.starts_with('#') {
+            lines.push(line.to_string());
+        }
+    }
+    Ok(lines)
+}
+
+fn write<W: Write>(out: &mut W, rank: &PageRank, output: Output) -> Result<()> {
+    match output {
+        Output::Table => table(out, rank)?,
+        Output::Json => {
+            serde_json::to_writer_pretty(&mut *out, rank).map_err(io::Error::from)?;
+            writeln!(out)?;
+        }
+        Output::Ndjson => {
+            for response in rank {
+                serde_json::to_writer(&mut *out, response).map_err(io::Error::from)?;
+                writeln!(out)?;
+            }
+        }
+        Output::Csv => {
+            writeln!(out, "domain,status_code,rank,page_rank_decimal,page_rank_integer,error")?;
+            for response in rank {
+                let [domain, status, position, decimal, integer, error] = row(response);
+                writeln!(out, "{},{},{},{},{},{}", csv(&domain), status, position, decimal, integer, csv(&error))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn row(response: &Response) -> [String; 6] {
+    [
+        response.domain.clone(),
+        response.status_code.to_string(),
+        response.rank.map(|x| x.to_string()).unwrap_or_default(),
+        format!("{:.2}", response.page_rank_decimal),
+        response.page_rank_integer.to_string(),
+        response.error.clone(),
+    ]
+}
+
+fn table<W: Write>(out: &mut W, rank: &PageRank) -> io::Result<()> {
+    let header = ["DOMAIN", "STATUS", "RANK", "DECIMAL", "INTEGER", "ERROR"].map(String::from);
+    let rows = std::iter::once(header).chain(rank.iter().map(row)).collect::<Vec<_>>();
+
+    let mut widths = [0; 6];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in &rows {
+        let line = row.iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+// quoted only when needed, per RFC 4180
+fn csv(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rank() -> PageRank {
+        let found = Response {
+            status_code: 200,
+            error: String::new(),
+            page_rank_integer: 5,
+            page_rank_decimal: 4.5,
+            rank: Some(1234),
+            domain: "example.com".to_string(),
+        };
+        let missing = Response {
+            status_code: 404,
+            error: "Domain not found, try again".to_string(),
+            page_rank_integer: 0,
+            page_rank_decimal: 0.0,
+            rank: None,
+            domain: "missing.com".to_string(),
+        };
+        PageRank::new(200, vec![found, missing], "4th Jan 2024".to_string())
+    }
+
+    fn output(output: Output) -> String {
+        let mut out = Vec::new();
+        write(&mut out, &rank(), output).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_config() {
+        let config = Config::parse("# pagerank\napi_key = \"secret\" # comment\n\nbase_url = 'http://a#b'\n").unwrap();
+        assert_eq!(config, Config { api_key: Some("secret".to_string()), base_url: Some("http://a#b".to_string()) });
+        assert_eq!(Config::parse(r#"api_key = "a\"b\\c""#).unwrap().api_key.as_deref(), Some(r#"a"b\c"#));
+        assert!(Config::parse("api_key").is_err());
+        assert!(Config::parse("api_key = secret").is_err());
+        assert!(Config::parse("api_key = \"secret\" trailing").is_err());
+        assert!(Config::parse("api_key = \"unterminated").is_err());
+    }
+
+    #[test]
+    fn test_read_lines() {
+        let lines = read_lines(io::Cursor::new(" a.com \n\n# skipped\nb.com")).unwrap();
+        assert_eq!(lines, ["a.com", "b.com"]);
+    }
+
+    #[test]
+    fn test_output() {
+        let table = output(Output::Table);
+        assert_eq!(table.lines().nth(1), Some("example.com  200     1234  4.50     5"));
+
+        let csv = output(Output::Csv);
+        assert_eq!(csv.lines().nth(2), Some("missing.com,404,,0.00,0,\"Domain not found, try again\""));
+
+        let ndjson = output(Output::Ndjson);
+        assert_eq!(ndjson.lines().count(), 2);
+        assert_eq!(serde_json::from_str::<Response>(ndjson.lines().next().unwrap()).unwrap().rank, Some(1234));
+
+        let json = serde_json::from_str::<PageRank>(&output(Output::Json)).unwrap();
+        assert_eq!(json, rank());
+    }
+}
@@ -0,0 +1,392 @@
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use reqwest::blocking::Client;
+use reqwest::header::HeaderMap;
+use reqwest::{Proxy, Url};
+
+use crate::client::{self, MAX_DOMAINS_PER_REQUEST};
+use crate::cache::{Cache, CacheLayer};
+use crate::fixture::{Recorder, Replayer};
+use crate::limit::RateLimiter;
+use crate::normalize::NormalizeOptions;
+use crate::plan::Plan;
+use crate::retry::Attempts;
+use crate::transport::{HttpRequest, HttpResponse};
+use crate::{Budget, CacheStats, PageRank, PageRankFirst, RateLimit, Result, RetryPolicy};
+
+// synchronous twin of crate::PageRankClient built on reqwest's blocking client.
+// must not be used from inside an async runtime.
+#[derive(Debug, Clone)]
+pub struct PageRankClient {
+    transport: Arc<dyn HttpTransport>,
+    endpoint: Url,
+    headers: HeaderMap,
+    timeout: Duration,
+    concurrency: usize,
+    retry: RetryPolicy,
+    limiter: Option<Arc<RateLimiter>>,
+    normalize: NormalizeOptions,
+    cache: Option<Arc<CacheLayer>>,
+}
+
+#[derive(Debug)]
+pub struct PageRankClientBuilder {
+    inner: client::PageRankClientBuilder,
+    transport: Option<Arc<dyn HttpTransport>>,
+}
+
+// blocking counterpart of crate::HttpTransport, see there
+pub trait HttpTransport: Send + Sync + fmt::Debug {
+    fn send(&self, request: HttpRequest) -> Result<HttpResponse>;
+}
+
+impl<T> HttpTransport for Arc<T>
+where T: HttpTransport + ?Sized
+{
+    fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+        (**self).send(request)
+    }
+}
+
+// blocking counterpart of crate::ReqwestTransport
+#[derive(Debug, Clone)]
+pub struct ReqwestTransport {
+    client: Client,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+impl HttpTransport for ReqwestTransport {
+    fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+        let response = self.client.get(request.url)
+            .headers(request.headers)
+            .timeout(request.timeout)
+            .send()?;
+
+        let status = response.status().as_u16();
+        let headers = response.headers().clone();
+        Ok(HttpResponse { status, headers, body: response.text()? })
+    }
+}
+
+impl PageRankClientBuilder {
+    pub fn base_url(self, base_url: &str) -> Self {
+        Self { inner: self.inner.base_url(base_url), ..self }
+    }
+
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self { inner: self.inner.timeout(timeout), ..self }
+    }
+
+    // number of chunk requests rank_all keeps in flight, one thread each
+    pub fn concurrency(self, concurrency: usize) -> Self {
+        Self { inner: self.inner.concurrency(concurrency), ..self }
+    }
+
+    pub fn retry(self, retry: RetryPolicy) -> Self {
+        Self { inner: self.inner.retry(retry), ..self }
+    }
+
+    pub fn rate_limit(self, rate_limit: RateLimit) -> Self {
+        Self { inner: self.inner.rate_limit(rate_limit), ..self }
+    }
+
+    pub fn cache<C>(self, cache: C) -> Self
+    where C: Cache + 'static
+    {
+        Self { inner: self.inner.cache(cache), ..self }
+    }
+
+    pub fn cache_ttl(self, ttl: Duration) -> Self {
+        Self { inner: self.inner.cache_ttl(ttl), ..self }
+    }
+
+    pub fn negative_cache_ttl(self, ttl: Duration) -> Self {
+        Self { inner: self.inner.negative_cache_ttl(ttl), ..self }
+    }
+
+    pub fn offline(self, offline: bool) -> Self {
+        Self { inner: self.inner.offline(offline), ..self }
+    }
+
+    pub fn normalize(self, options: NormalizeOptions) -> Self {
+        Self { inner: self.inner.normalize(options), ..self }
+    }
+
+    pub fn user_agent(self, user_agent: &str) -> Self {
+        Self { inner: self.inner.user_agent(user_agent), ..self }
+    }
+
+    pub fn proxy(self, proxy: Proxy) -> Self {
+        Self { inner: self.inner.proxy(proxy), ..self }
+    }
+
+    pub fn danger_accept_invalid_certs(self, accept: bool) -> Self {
+        Self { inner: self.inner.danger_accept_invalid_certs(accept), ..self }
+    }
+
+    pub fn add_root_certificate_pem<P>(self, path: P) -> Self
+    where P: AsRef<Path>
+    {
+        Self { inner: self.inner.add_root_certificate_pem(path), ..self }
+    }
+
+    pub fn pin_sha256(self, fingerprint: &str) -> Self {
+        Self { inner: self.inner.pin_sha256(fingerprint), ..self }
+    }
+
+    pub fn record<P>(self, path: P) -> Self
+    where P: AsRef<Path>
+    {
+        Self { inner: self.inner.record(path), ..self }
+    }
+
+    pub fn replay<P>(self, path: P) -> Self
+    where P: AsRef<Path>
+    {
+        Self { inner: self.inner.replay(path), ..self }
+    }
+
+    // see crate::PageRankClientBuilder::transport
+    pub fn transport<T>(mut self, transport: T) -> Self
+    where T: HttpTransport + 'static
+    {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    fn reqwest_transport(inner: &client::PageRankClientBuilder) -> Result<Arc<dyn HttpTransport>> {
+        let mut builder = inner.tls.resolve()?.apply_blocking(Client::builder());
+        if let Some(user_agent) = &inner.user_agent {
+            builder = builder.user_agent(user_agent.as_str());
+        }
+        if let Some(proxy) = &inner.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        Ok(Arc::new(ReqwestTransport::new(builder.build()?)))
+    }
+
+    pub fn build(self) -> Result<PageRankClient> {
+        let inner = self.inner;
+        let endpoint = client::endpoint(&inner.base_url)?;
+        let headers = client::headers(&inner.key)?;
+        let cache = inner.cache_layer()?;
+
+        let transport = match self.transport {
+            Some(transport) => transport,
+            None => Self::reqwest_transport(&inner)?,
+        };
+        let transport: Arc<dyn HttpTransport> = match inner.load_fixtures()? {
+            Some(fixtures) if fixtures.is_replay() => Arc::new(Replayer::new(fixtures)),
+            Some(fixtures) => Arc::new(Recorder::new(transport, fixtures)),
+            None => transport,
+        };
+
+        Ok(PageRankClient {
+            transport,
+            endpoint,
+            headers,
+            timeout: inner.timeout,
+            concurrency: inner.concurrency,
+            retry: inner.retry,
+            limiter: inner.rate_limit.map(|x| Arc::new(RateLimiter::new(x))),
+            normalize: inner.normalize,
+            cache,
+        })
+    }
+}
+
+impl PageRankClient {
+    pub fn new(key: &str) -> Result<Self> {
+        Self::builder(key).build()
+    }
+
+    pub fn builder(key: &str) -> PageRankClientBuilder {
+        PageRankClientBuilder { inner: client::PageRankClientBuilder::new(key), transport: None }
+    }
+
+    // one request, like the async rank() it refuses more than MAX_DOMAINS_PER_REQUEST unique domains
+    pub fn rank<I, T>(&self, domains: I) -> Result<PageRank>
+    where I: IntoIterator<Item = T>, T: AsRef<str>
+    {
+        let plan = Plan::new(domains, &self.normalize);
+        let rank = self.fetch(plan.single_request()?)?;
+        Ok(plan.fan_out(rank))
+    }
+
+    fn fetch(&self, domains: &[String]) -> Result<PageRank> {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.fetch_remote(domains),
+        };
+        let (cached, misses) = cache.lookup(domains)?;
+        let rank = self.fetch_remote(&misses)?;
+        cache.complete(cached, &misses, rank)
+    }
+
+    fn fetch_remote(&self, domains: &[String]) -> Result<PageRank> {
+        if domains.is_empty() {
+            return Ok(client::empty())
+        }
+
+        let mut attempts = Attempts::new(&self.retry, self.limiter.as_deref());
+        loop {
+            while let Some(wait) = attempts.throttle()? {
+                thread::sleep(wait);
+            }
+            match self.send(domains) {
+                Ok(rank) => return Ok(rank),
+                Err(error) => thread::sleep(attempts.failed(error)?),
+            }
+        }
+    }
+
+    fn send(&self, domains: &[String]) -> Result<PageRank> {
+        client::read(self.transport.send(client::request(&self.endpoint, &self.headers, domains, self.timeout))?)
+    }
+
+    pub fn remaining_budget(&self) -> Option<Budget> {
+        self.limiter.as_ref().map(|x| x.remaining())
+    }
+
+    pub fn cache_stats(&self) -> Result<Option<CacheStats>> {
+        self.cache.as_ref().map(|x| x.stats()).transpose()
+    }
+
+    pub fn rank_one<T>(&self, domain: T) -> Result<PageRankFirst>
+    where T: AsRef<str>
+    {
+        self.rank(Some(domain))?.try_into()
+    }
+
+    #[cfg(feature = "url")]
+    pub fn rank_url(&self, url: &url::Url) -> Result<PageRankFirst> {
+        self.rank_one(crate::normalize::url_host(url))
+    }
+
+    #[cfg(feature = "url")]
+    pub fn rank_urls<'u, I>(&self, urls: I) -> Result<PageRank>
+    where I: IntoIterator<Item = &'u url::Url>
+    {
+        self.rank_all(urls.into_iter().map(crate::normalize::url_host))
+    }
+
+    // same chunking as the async client, up to `concurrency` chunks run on scoped threads
+    pub fn rank_all<I, T>(&self, domains: I) -> Result<PageRank>
+    where I: IntoIterator<Item = T>, T: AsRef<str>
+    {
+        let plan = Plan::new(domains, &self.normalize);
+        let chunks = plan.unique().chunks(MAX_DOMAINS_PER_REQUEST).collect::<Vec<_>>();
+
+        let mut ranks = Vec::with_capacity(chunks.len());
+        for group in chunks.chunks(self.concurrency) {
+            let results = thread::scope(|s| {
+                let handles = group.iter()
+                    .map(|chunk| s.spawn(move || self.fetch(chunk)))
+                    .collect::<Vec<_>>();
+                handles.into_iter().map(|x| x.join().unwrap()).collect::<Vec<_>>()
+            });
+            for rank in results {
+                ranks.push(rank?);
+            }
+        }
+
+        Ok(plan.fan_out(client::merge(ranks)))
+    }
+
+    pub fn rank_batch<I, D, T>(&self, batches: I) -> Result<Vec<PageRank>>
+    where I: IntoIterator<Item = D>, D: IntoIterator<Item = T>, T: AsRef<str>
+    {
+        batches.into_iter().map(|batch| self.rank(batch)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockServer};
+    use crate::Error;
+
+    #[test]
+    fn test_rank_all() {
+        let server = MockServer::start(testing::echo);
+        let client = PageRankClient::builder("key").base_url(server.url()).concurrency(2).build().unwrap();
+        let domains = (0..250).map(|i| format!("domain{}.com", i)).collect::<Vec<_>>();
+
+        let rank = client.rank_all(&domains).unwrap();
+        assert_eq!(rank.response.iter().map(|r| r.domain.clone()).collect::<Vec<_>>(), domains);
+        assert_eq!(server.requests().len(), 3);
+        assert!(matches!(client.rank(&domains), Err(Error::Config(_))));
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn test_rank_one() {
+        let server = MockServer::start(testing::echo);
+        let client = PageRankClient::builder("key").base_url(server.url()).build().unwrap();
+        assert_eq!(client.rank_one("example.com").unwrap().response.domain, "example.com");
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        let server = MockServer::start(|_| testing::Reply::status(401, String::new()));
+        let client = PageRankClient::builder("key").base_url(server.url()).build().unwrap();
+        assert!(matches!(client.rank(vec!["example.com"]), Err(Error::InvalidKey)));
+    }
+
+    // answers from memory and keeps every request
+    #[derive(Debug, Default)]
+    struct Fake {
+        requests: std::sync::Mutex<Vec<HttpRequest>>,
+    }
+
+    impl HttpTransport for Fake {
+        fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+            let body = testing::body(request.domains().iter().map(|x| testing::found(x)).collect());
+            self.requests.lock().unwrap().push(request);
+            Ok(HttpResponse { status: 200, headers: HeaderMap::new(), body })
+        }
+    }
+
+    #[test]
+    fn test_transport() {
+        let fake = Arc::new(Fake::default());
+        let client = PageRankClient::builder("key")
+            .base_url("https://openpagerank.com/api/v1.0")
+            .transport(fake.clone())
+            .build()
+            .unwrap();
+
+        assert!(client.rank_one("https://a.com/").unwrap().is_found());
+        let requests = fake.requests.lock().unwrap();
+        assert_eq!(requests[0].domains(), ["a.com"]);
+        assert_eq!(requests[0].headers["api-opr"], "key");
+    }
+
+    #[test]
+    fn test_record_replay() {
+        let path = testing::temp_path("blocking-fixtures.jsonl");
+
+        let fake = Arc::new(Fake::default());
+        let recorder = PageRankClient::builder("secret-key")
+            .base_url("https://openpagerank.com/api/v1.0")
+            .transport(fake.clone())
+            .record(&path)
+            .build()
+            .unwrap();
+        let recorded = recorder.rank(vec!["a.com", "b.com"]).unwrap();
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("secret-key"));
+
+        let replayer = PageRankClient::builder("key").base_url("http://127.0.0.1:9").replay(&path).build().unwrap();
+        assert_eq!(replayer.rank(vec!["a.com", "b.com"]).unwrap(), recorded);
+        assert_eq!(fake.requests.lock().unwrap().len(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
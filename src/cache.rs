@@ -0,0 +1,399 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::plan::align;
+use crate::{Error, PageRank, Response, Result};
+
+mod file;
+mod memory;
+#[cfg(feature = "redis")]
+mod redis;
+
+pub use file::FileCache;
+pub use memory::MemoryCache;
+#[cfg(feature = "redis")]
+pub use self::redis::RedisCache;
+
+pub(crate) const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+pub(crate) const DEFAULT_NEGATIVE_TTL: Duration = Duration::from_secs(60 * 60);
+
+// storage behind the client cache, keyed by normalized domain. backends only store and
+// return entries, expiry and dataset changes are decided by the client.
+// the trait is synchronous: the async client runs every call through tokio's spawn_blocking,
+// so backends are free to do blocking io.
+pub trait Cache: Send + Sync + fmt::Debug {
+    fn get(&self, domain: &str) -> Result<Option<CacheEntry>>;
+
+    // replaces any entry for the same domain. the client stops serving the entry after `ttl`,
+    // backends with their own expiry may drop it from then on.
+    fn set(&self, entry: CacheEntry, ttl: Duration) -> Result<()>;
+
+    // one entry per domain, in order. the client looks up a whole chunk at once so
+    // remote backends can answer it in a single round trip.
+    fn get_many(&self, domains: &[String]) -> Result<Vec<Option<CacheEntry>>> {
+        domains.iter().map(|x| self.get(x)).collect()
+    }
+
+    fn set_many(&self, entries: Vec<(CacheEntry, Duration)>) -> Result<()> {
+        entries.into_iter().try_for_each(|(entry, ttl)| self.set(entry, ttl))
+    }
+
+    fn clear(&self) -> Result<()>;
+
+    // may need a round trip for remote backends, which then fail instead of reporting 0
+    fn len(&self) -> Result<usize>;
+
+    fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    // hits and misses are counted by the client, backends report what they hold
+    fn stats(&self) -> Result<CacheStats> {
+        Ok(CacheStats { entries: self.len()?, ..CacheStats::default() })
+    }
+}
+
+// shares one backend between several clients
+impl<C> Cache for Arc<C>
+where C: Cache + ?Sized
+{
+    fn get(&self, domain: &str) -> Result<Option<CacheEntry>> {
+        (**self).get(domain)
+    }
+
+    fn set(&self, entry: CacheEntry, ttl: Duration) -> Result<()> {
+        (**self).set(entry, ttl)
+    }
+
+    fn get_many(&self, domains: &[String]) -> Result<Vec<Option<CacheEntry>>> {
+        (**self).get_many(domains)
+    }
+
+    fn set_many(&self, entries: Vec<(CacheEntry, Duration)>) -> Result<()> {
+        (**self).set_many(entries)
+    }
+
+    fn clear(&self) -> Result<()> {
+        (**self).clear()
+    }
+
+    fn len(&self) -> Result<usize> {
+        (**self).len()
+    }
+
+    fn stats(&self) -> Result<CacheStats> {
+        (**self).stats()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CacheEntry {
+    // normalized domain the response was requested for
+    pub domain: String,
+    pub response: Response,
+    // unix seconds
+    pub fetched_at: u64,
+    // dataset the response came from, PageRank::last_updated
+    pub last_updated: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    // entries dropped to make room
+    pub evictions: u64,
+    pub entries: usize,
+}
+
+impl CacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+}
+
+// expiry and offline handling on top of a Cache, owned by the client and its clones
+#[derive(Debug)]
+pub(crate) struct CacheLayer {
+    cache: Arc<dyn Cache>,
+    ttl: Duration,
+    negative_ttl: Duration,
+    offline: bool,
+    // last_updated of the latest api answer, entries from an older dataset are misses
+    dataset: Mutex<String>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CacheLayer {
+    pub(crate) fn new(cache: Arc<dyn Cache>, ttl: Duration, negative_ttl: Duration, offline: bool) -> Self {
+        Self {
+            cache,
+            ttl,
+            negative_ttl,
+            offline,
+            dataset: Mutex::new(String::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    // how long a response may be served from the cache, None when it is not cached at all
+    fn ttl(&self, response: &Response) -> Option<Duration> {
+        let ttl = match response.status_code {
+            200 => self.ttl,
+            404 => self.negative_ttl,
+            _ => return None,
+        };
+        (!ttl.is_zero()).then_some(ttl)
+    }
+
+    // offline everything stored is served, however old
+    fn is_fresh(&self, entry: &CacheEntry) -> bool {
+        if self.offline {
+            return true
+        }
+
+        let dataset = self.dataset.lock().unwrap();
+        if !dataset.is_empty() && *dataset != entry.last_updated {
+            return false
+        }
+        match self.ttl(&entry.response) {
+            Some(ttl) => now().saturating_sub(entry.fetched_at) < ttl.as_secs(),
+            None => false,
+        }
+    }
+
+    // cached entries in domain order, plus the domains that still need a request.
+    // offline a single miss fails the lookup.
+    pub(crate) fn lookup(&self, domains: &[String]) -> Result<(Vec<Option<CacheEntry>>, Vec<String>)> {
+        let mut cached = Vec::with_capacity(domains.len());
+        let mut misses = Vec::new();
+
+        for (domain, entry) in domains.iter().zip(self.cache.get_many(domains)?) {
+            match entry.filter(|x| self.is_fresh(x)) {
+                Some(entry) => {
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+                    cached.push(Some(entry));
+                }
+                None => {
+                    self.misses.fetch_add(1, Ordering::Relaxed);
+                    misses.push(domain.clone());
+                    cached.push(None);
+                }
+            }
+        }
+
+        if self.offline && !misses.is_empty() {
+            return Err(Error::NotCached(misses))
+        }
+        Ok((cached, misses))
+    }
+
+    // stores the fresh responses and slots them back in between the cached ones
+    pub(crate) fn complete(&self, cached: Vec<Option<CacheEntry>>, misses: &[String], rank: PageRank) -> Result<PageRank> {
+        let aligned = align(misses, &rank.response);
+
+        if !rank.last_updated.is_empty() {
+            *self.dataset.lock().unwrap() = rank.last_updated.clone();
+        }
+
+        let fetched_at = now();
+        let entries = misses.iter()
+            .zip(aligned.iter().copied())
+            .filter_map(|(domain, response)| {
+                let response = response?;
+                let entry = CacheEntry {
+                    domain: domain.clone(),
+                    response: response.clone(),
+                    fetched_at,
+                    last_updated: rank.last_updated.clone(),
+                };
+                Some((entry, self.ttl(response)?))
+            })
+            .collect::<Vec<_>>();
+        if !entries.is_empty() {
+            self.cache.set_many(entries)?;
+        }
+
+        let last_updated = match rank.last_updated.is_empty() {
+            true => cached.iter().flatten().next().map(|x| x.last_updated.clone()).unwrap_or_default(),
+            false => rank.last_updated.clone(),
+        };
+
+        let mut fresh = aligned.into_iter();
+        let response = cached.into_iter()
+            .filter_map(|cached| match cached {
+                Some(entry) => Some(entry.response),
+                None => fresh.next().flatten().cloned(),
+            })
+            .collect();
+
+        Ok(PageRank::new(rank.status_code, response, last_updated))
+    }
+
+    pub(crate) fn stats(&self) -> Result<CacheStats> {
+        Ok(CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            ..self.cache.stats()?
+        })
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_secs()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, aw, MockServer};
+    use crate::PageRankClient;
+
+    fn layer(offline: bool) -> CacheLayer {
+        CacheLayer::new(Arc::new(MemoryCache::new(10)), DEFAULT_TTL, DEFAULT_NEGATIVE_TTL, offline)
+    }
+
+    #[test]
+    fn test_ttl() {
+        let layer = layer(false);
+        layer.cache.set(testing::cache_entry("fresh.com", now() - 60), DEFAULT_TTL).unwrap();
+        layer.cache.set(testing::cache_entry("stale.com", now() - 2 * DEFAULT_TTL.as_secs()), DEFAULT_TTL).unwrap();
+
+        let (cached, misses) = layer.lookup(&["fresh.com".to_string(), "stale.com".to_string()]).unwrap();
+        assert!(cached[0].is_some());
+        assert_eq!(misses, ["stale.com"]);
+        assert_eq!((layer.stats().unwrap().hits, layer.stats().unwrap().misses), (1, 1));
+    }
+
+    #[test]
+    fn test_negative() {
+        let domains = vec!["missing.com".to_string(), "broken.com".to_string()];
+        let body = testing::body(vec![
+            testing::not_found("missing.com"),
+            r#"{"status_code":500,"error":"boom","page_rank_integer":0,"page_rank_decimal":0,"rank":null,"domain":"broken.com"}"#.to_string(),
+        ]);
+
+        let layer = layer(false);
+        layer.complete(vec![None, None], &domains, serde_json::from_str(&body).unwrap()).unwrap();
+        assert_eq!(layer.cache.get("missing.com").unwrap().unwrap().response.status_code, 404);
+        assert!(layer.cache.get("broken.com").unwrap().is_none());
+
+        let layer = CacheLayer::new(Arc::new(MemoryCache::new(10)), DEFAULT_TTL, Duration::ZERO, false);
+        layer.complete(vec![None, None], &domains, serde_json::from_str(&body).unwrap()).unwrap();
+        assert!(layer.cache.is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_new_dataset() {
+        let layer = layer(false);
+        layer.cache.set(testing::cache_entry("a.com", now()), DEFAULT_TTL).unwrap();
+        let rank: PageRank = serde_json::from_str(&testing::body(vec![testing::found("b.com")])).unwrap();
+        let rank = layer.complete(vec![None], &["b.com".to_string()], PageRank { last_updated: "4th Feb 2024".to_string(), ..rank }).unwrap();
+        assert_eq!(rank.last_updated, "4th Feb 2024");
+
+        let (_, misses) = layer.lookup(&["a.com".to_string(), "b.com".to_string()]).unwrap();
+        assert_eq!(misses, ["a.com"]);
+    }
+
+    #[test]
+    fn test_offline() {
+        let layer = layer(true);
+        layer.cache.set(CacheEntry { last_updated: "4th Jan 2020".to_string(), ..testing::cache_entry("old.com", 0) }, DEFAULT_TTL).unwrap();
+        let (cached, _) = layer.lookup(&["old.com".to_string()]).unwrap();
+        assert_eq!(cached[0].as_ref().unwrap().last_updated, "4th Jan 2020");
+
+        match layer.lookup(&["old.com".to_string(), "new.com".to_string()]) {
+            Err(Error::NotCached(missing)) => assert_eq!(missing, ["new.com"]),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_client() {
+        let server = MockServer::start(testing::echo);
+        let client = PageRankClient::builder("key")
+            .base_url(server.url())
+            .cache(MemoryCache::new(100))
+            .build()
+            .unwrap();
+
+        aw!(client.rank(vec!["a.com", "b.com"])).unwrap();
+        let rank = aw!(client.rank(vec!["https://b.com/", "c.com", "a.com"])).unwrap();
+        let domains = rank.response.iter().map(|x| x.domain.as_str()).collect::<Vec<_>>();
+        assert_eq!(domains, ["b.com", "c.com", "a.com"]);
+        assert_eq!(rank.last_updated, "4th Jan 2024");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].domains, ["c.com"]);
+
+        aw!(client.rank(vec!["a.com"])).unwrap();
+        assert_eq!(server.requests().len(), 2);
+        let stats = client.cache_stats().unwrap().unwrap();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (3, 3, 3));
+    }
+
+    #[test]
+    fn test_offline_client() {
+        let cache = Arc::new(MemoryCache::new(100));
+        let server = MockServer::start(testing::echo);
+        let online = PageRankClient::builder("key").base_url(server.url()).cache(cache.clone()).build().unwrap();
+        aw!(online.rank(vec!["a.com", "b.com"])).unwrap();
+
+        let offline = PageRankClient::builder("key").base_url("http://127.0.0.1:9").cache(cache).offline(true).build().unwrap();
+        let rank = aw!(offline.rank(vec!["b.com", "a.com", "not a domain"])).unwrap();
+        assert_eq!(rank.response[0].domain, "b.com");
+        assert_eq!(rank.response[2].status_code, 400);
+        assert!(matches!(aw!(offline.rank(vec!["c.com"])), Err(Error::NotCached(_))));
+        assert_eq!(server.requests().len(), 1);
+
+        assert!(PageRankClient::builder("key").offline(true).build().is_err());
+    }
+
+    // a remote backend taking its time
+    #[derive(Debug)]
+    struct Slow(MemoryCache);
+
+    impl Cache for Slow {
+        fn get(&self, domain: &str) -> Result<Option<CacheEntry>> {
+            std::thread::sleep(Duration::from_millis(300));
+            self.0.get(domain)
+        }
+
+        fn set(&self, entry: CacheEntry, ttl: Duration) -> Result<()> {
+            self.0.set(entry, ttl)
+        }
+
+        fn clear(&self) -> Result<()> {
+            self.0.clear()
+        }
+
+        fn len(&self) -> Result<usize> {
+            self.0.len()
+        }
+    }
+
+    #[test]
+    fn test_slow_backend() {
+        let server = MockServer::start(testing::echo);
+        let client = PageRankClient::builder("key").base_url(server.url()).cache(Slow(MemoryCache::new(10))).build().unwrap();
+
+        // the timer only fires on time when the lookup is not blocking the single runtime thread
+        let start = std::time::Instant::now();
+        let (rank, elapsed) = aw!(async {
+            futures::join!(client.rank(vec!["a.com"]), async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                start.elapsed()
+            })
+        });
+        assert!(rank.is_ok());
+        assert!(elapsed < Duration::from_millis(250), "{:?}", elapsed);
+    }
+}
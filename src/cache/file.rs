@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::{Cache, CacheEntry};
+use crate::Result;
+
+// append-only json lines file, one CacheEntry per line. the file is read once on open and
+// later lines win, so a run can pick up where an earlier one stopped or replay it offline.
+#[derive(Debug)]
+pub struct FileCache {
+    path: PathBuf,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    entries: HashMap<String, CacheEntry>,
+    file: File,
+}
+
+impl FileCache {
+    pub fn open<P>(path: P) -> Result<Self>
+    where P: AsRef<Path>
+    {
+        let path = path.as_ref().to_path_buf();
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        // a line cut short by a crash is skipped
+        let entries = content.lines()
+            .filter_map(|line| serde_json::from_str::<CacheEntry>(line).ok())
+            .map(|entry| (entry.domain.clone(), entry))
+            .collect();
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        if !content.is_empty() && !content.ends_with('\n') {
+            file.write_all(b"\n")?;
+        }
+
+        Ok(Self { path, state: Mutex::new(State { entries, file }) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // rewrites the file with a single line per domain
+    pub fn compact(&self) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        let tmp = self.path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        for entry in state.entries.values() {
+            file.write_all(&line(entry)?)?;
+        }
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)?;
+
+        state.file = OpenOptions::new().append(true).open(&self.path)?;
+        Ok(())
+    }
+}
+
+fn line(entry: &CacheEntry) -> io::Result<Vec<u8>> {
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    Ok(line)
+}
+
+impl Cache for FileCache {
+    fn get(&self, domain: &str) -> Result<Option<CacheEntry>> {
+        Ok(self.state.lock().unwrap().entries.get(domain).cloned())
+    }
+
+    fn set(&self, entry: CacheEntry, _ttl: Duration) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.file.write_all(&line(&entry)?)?;
+        state.entries.insert(entry.domain.clone(), entry);
+        Ok(())
+    }
+
+    // a single write per chunk
+    fn set_many(&self, entries: Vec<(CacheEntry, Duration)>) -> Result<()> {
+        let mut lines = Vec::new();
+        for (entry, _) in &entries {
+            lines.extend(line(entry)?);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.file.write_all(&lines)?;
+        state.entries.extend(entries.into_iter().map(|(entry, _)| (entry.domain.clone(), entry)));
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.file.set_len(0)?;
+        state.entries.clear();
+        Ok(())
+    }
+
+    fn len(&self) -> Result<usize> {
+        Ok(self.state.lock().unwrap().entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn test_reopen() {
+        let path = testing::temp_path("reopen.jsonl");
+        let cache = FileCache::open(&path).unwrap();
+        cache.set(testing::cache_entry("a.com", 1), Duration::ZERO).unwrap();
+        cache.set(testing::cache_entry("b.com", 1), Duration::ZERO).unwrap();
+        cache.set(testing::cache_entry("a.com", 2), Duration::ZERO).unwrap();
+        drop(cache);
+
+        let cache = FileCache::open(&path).unwrap();
+        assert_eq!(cache.len().unwrap(), 2);
+        assert_eq!(cache.get("a.com").unwrap().unwrap().fetched_at, 2);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 3);
+
+        cache.compact().unwrap();
+        cache.set(testing::cache_entry("c.com", 3), Duration::ZERO).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 3);
+        assert_eq!(FileCache::open(&path).unwrap().len().unwrap(), 3);
+
+        cache.clear().unwrap();
+        assert!(FileCache::open(&path).unwrap().is_empty().unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_truncated_line() {
+        let path = testing::temp_path("truncated.jsonl");
+        let line = serde_json::to_string(&testing::cache_entry("a.com", 1)).unwrap();
+        fs::write(&path, format!("{}\n{}", line, &line[..20])).unwrap();
+
+        let cache = FileCache::open(&path).unwrap();
+        assert_eq!(cache.len().unwrap(), 1);
+        cache.set(testing::cache_entry("b.com", 1), Duration::ZERO).unwrap();
+        assert_eq!(FileCache::open(&path).unwrap().len().unwrap(), 2);
+        fs::remove_file(&path).unwrap();
+    }
+}
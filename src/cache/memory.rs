@@ -0,0 +1,107 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::{Cache, CacheEntry, CacheStats};
+use crate::Result;
+
+// bounded in memory cache, when full the least recently used entry makes room
+#[derive(Debug)]
+pub struct MemoryCache {
+    capacity: usize,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    entries: HashMap<String, (CacheEntry, u64)>,
+    // last use -> domain, the first key is the least recently used
+    order: BTreeMap<u64, String>,
+    tick: u64,
+    evictions: u64,
+}
+
+impl State {
+    fn touch(&mut self, domain: &str) {
+        self.tick += 1;
+        if let Some((_, used)) = self.entries.get_mut(domain) {
+            self.order.remove(&*used);
+            *used = self.tick;
+            self.order.insert(self.tick, domain.to_string());
+        }
+    }
+}
+
+impl MemoryCache {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity: capacity.max(1), state: Mutex::new(State::default()) }
+    }
+}
+
+impl Cache for MemoryCache {
+    fn get(&self, domain: &str) -> Result<Option<CacheEntry>> {
+        let mut state = self.state.lock().unwrap();
+        state.touch(domain);
+        Ok(state.entries.get(domain).map(|(entry, _)| entry.clone()))
+    }
+
+    fn set(&self, entry: CacheEntry, _ttl: Duration) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let domain = entry.domain.clone();
+
+        state.tick += 1;
+        let used = state.tick;
+        if let Some((_, old)) = state.entries.insert(domain.clone(), (entry, used)) {
+            state.order.remove(&old);
+        }
+        state.order.insert(used, domain);
+
+        while state.entries.len() > self.capacity {
+            let Some((_, oldest)) = state.order.pop_first() else { break };
+            state.entries.remove(&oldest);
+            state.evictions += 1;
+        }
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.entries.clear();
+        state.order.clear();
+        Ok(())
+    }
+
+    fn len(&self) -> Result<usize> {
+        Ok(self.state.lock().unwrap().entries.len())
+    }
+
+    fn stats(&self) -> Result<CacheStats> {
+        let state = self.state.lock().unwrap();
+        Ok(CacheStats { evictions: state.evictions, entries: state.entries.len(), ..CacheStats::default() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn test_lru() {
+        let cache = MemoryCache::new(2);
+        cache.set(testing::cache_entry("a.com", 0), Duration::ZERO).unwrap();
+        cache.set(testing::cache_entry("b.com", 0), Duration::ZERO).unwrap();
+        assert!(cache.get("a.com").unwrap().is_some());
+        cache.set(testing::cache_entry("c.com", 0), Duration::ZERO).unwrap();
+
+        assert!(cache.get("b.com").unwrap().is_none());
+        assert!(cache.get("a.com").unwrap().is_some());
+        assert!(cache.get("c.com").unwrap().is_some());
+        assert_eq!(cache.stats().unwrap(), CacheStats { hits: 0, misses: 0, evictions: 1, entries: 2 });
+
+        cache.set(testing::cache_entry("c.com", 0), Duration::ZERO).unwrap();
+        assert_eq!(cache.len().unwrap(), 2);
+        cache.clear().unwrap();
+        assert!(cache.is_empty().unwrap());
+    }
+}
@@ -0,0 +1,646 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use reqwest::{Client, Proxy, Url};
+use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+
+use crate::{Budget, CacheStats, Error, PageRank, PageRankFirst, RateLimit, Response, Result, RetryPolicy};
+use crate::cache::{self, Cache, CacheLayer};
+use crate::fixture::{Fixtures, Recorder, Replayer};
+use crate::limit::RateLimiter;
+use crate::normalize::NormalizeOptions;
+use crate::plan::Plan;
+use crate::retry::Attempts;
+use crate::tls::TlsSettings;
+use crate::transport::{HttpRequest, HttpResponse, HttpTransport, ReqwestTransport};
+
+const API_ROOT: &str = "https://openpagerank.com/api/v1.0";
+const API_ROOT_ENV: &str = "OPENPAGERANK_API_ROOT";
+const ENDPOINT: &str = "getPageRank";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// open rank api rejects more than 100 domains per request
+pub const MAX_DOMAINS_PER_REQUEST: usize = 100;
+
+// long lived client, build once and share it (cheap to clone) to reuse pooled connections
+#[derive(Debug, Clone)]
+pub struct PageRankClient {
+    transport: Arc<dyn HttpTransport>,
+    endpoint: Url,
+    headers: HeaderMap,
+    timeout: Duration,
+    concurrency: usize,
+    retry: RetryPolicy,
+    limiter: Option<Arc<RateLimiter>>,
+    normalize: NormalizeOptions,
+    cache: Option<Arc<CacheLayer>>,
+}
+
+#[derive(Debug)]
+pub struct PageRankClientBuilder {
+    pub(crate) key: String,
+    pub(crate) base_url: String,
+    pub(crate) timeout: Duration,
+    pub(crate) concurrency: usize,
+    pub(crate) retry: RetryPolicy,
+    pub(crate) rate_limit: Option<RateLimit>,
+    pub(crate) cache: Option<Arc<dyn Cache>>,
+    pub(crate) cache_ttl: Duration,
+    pub(crate) negative_cache_ttl: Duration,
+    pub(crate) offline: bool,
+    pub(crate) fixtures: Option<(PathBuf, bool)>,
+    pub(crate) transport: Option<Arc<dyn HttpTransport>>,
+    pub(crate) normalize: NormalizeOptions,
+    pub(crate) user_agent: Option<String>,
+    pub(crate) proxy: Option<Proxy>,
+    pub(crate) tls: TlsSettings,
+}
+
+impl PageRankClientBuilder {
+    pub(crate) fn new(key: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            base_url: env::var(API_ROOT_ENV).unwrap_or_else(|_| API_ROOT.to_string()),
+            timeout: DEFAULT_TIMEOUT,
+            concurrency: 1,
+            retry: RetryPolicy::none(),
+            rate_limit: None,
+            cache: None,
+            cache_ttl: cache::DEFAULT_TTL,
+            negative_cache_ttl: cache::DEFAULT_NEGATIVE_TTL,
+            offline: false,
+            fixtures: None,
+            transport: None,
+            normalize: NormalizeOptions::default(),
+            user_agent: None,
+            proxy: None,
+            tls: TlsSettings::default(),
+        }
+    }
+
+    // api root the endpoint is resolved against, overrides OPENPAGERANK_API_ROOT
+    pub fn base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.to_string();
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // number of chunk requests rank_all keeps in flight
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    // no retries unless a policy is set
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    // shared by every request of this client and its clones, including concurrent chunks
+    pub fn rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    // answer repeated lookups from a cache, shared by this client and its clones.
+    // pass an Arc to share one cache between clients.
+    pub fn cache<C>(mut self, cache: C) -> Self
+    where C: Cache + 'static
+    {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    // how long found domains are served from the cache, 24h by default.
+    // cached responses from an older dataset (last_updated) are never served.
+    pub fn cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    // same for not found domains, 1h by default, Duration::ZERO never caches them
+    pub fn negative_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.negative_cache_ttl = ttl;
+        self
+    }
+
+    // answer from the cache only, regardless of age, and fail with Error::NotCached
+    // instead of sending a request. needs a cache.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    // how inputs are cleaned up before querying, inputs that are no valid domain get a 400 response
+    pub fn normalize(mut self, options: NormalizeOptions) -> Self {
+        self.normalize = options;
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    // disables certificate verification entirely, the api key is sent to whoever answers
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.tls.accept_invalid_certs = accept;
+        self
+    }
+
+    // trust the certificates of a PEM bundle in addition to the built-in roots
+    pub fn add_root_certificate_pem<P>(mut self, path: P) -> Self
+    where P: AsRef<Path>
+    {
+        self.tls.root_certificates.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    // only accept a server certificate with this hex sha256 fingerprint, may be called repeatedly
+    pub fn pin_sha256(mut self, fingerprint: &str) -> Self {
+        self.tls.pins.push(fingerprint.to_string());
+        self
+    }
+
+    // write every api exchange as a json line to a fixture file, the API-OPR key is redacted.
+    // an existing file is appended to.
+    pub fn record<P>(mut self, path: P) -> Self
+    where P: AsRef<Path>
+    {
+        self.fixtures = Some((path.as_ref().to_path_buf(), false));
+        self
+    }
+
+    // answer from a file written by record() instead of the api. the same domains get their
+    // recorded answers in order, then the last one repeats. unrecorded domains fail with
+    // Error::Fixture.
+    pub fn replay<P>(mut self, path: P) -> Self
+    where P: AsRef<Path>
+    {
+        self.fixtures = Some((path.as_ref().to_path_buf(), true));
+        self
+    }
+
+    // send requests through `transport` instead of the built-in reqwest client, the tls, proxy
+    // and user agent settings only apply to the built-in one. pass an Arc to keep a handle.
+    pub fn transport<T>(mut self, transport: T) -> Self
+    where T: HttpTransport + 'static
+    {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    pub(crate) fn load_fixtures(&self) -> Result<Option<Fixtures>> {
+        match &self.fixtures {
+            Some((path, true)) => Ok(Some(Fixtures::replay(path)?)),
+            Some((path, false)) => Ok(Some(Fixtures::record(path)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn cache_layer(&self) -> Result<Option<Arc<CacheLayer>>> {
+        match &self.cache {
+            Some(cache) => {
+                let layer = CacheLayer::new(cache.clone(), self.cache_ttl, self.negative_cache_ttl, self.offline);
+                Ok(Some(Arc::new(layer)))
+            }
+            None if self.offline => Err(Error::Config("offline mode needs a cache".to_string())),
+            None => Ok(None),
+        }
+    }
+
+    fn reqwest_transport(&self) -> Result<Arc<dyn HttpTransport>> {
+        let mut builder = self.tls.resolve()?.apply(Client::builder());
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent.as_str());
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        Ok(Arc::new(ReqwestTransport::new(builder.build()?)))
+    }
+
+    pub fn build(self) -> Result<PageRankClient> {
+        let endpoint = endpoint(&self.base_url)?;
+        let headers = headers(&self.key)?;
+        let cache = self.cache_layer()?;
+
+        let transport = match &self.transport {
+            Some(transport) => transport.clone(),
+            None => self.reqwest_transport()?,
+        };
+        let transport: Arc<dyn HttpTransport> = match self.load_fixtures()? {
+            Some(fixtures) if fixtures.is_replay() => Arc::new(Replayer::new(fixtures)),
+            Some(fixtures) => Arc::new(Recorder::new(transport, fixtures)),
+            None => transport,
+        };
+
+        Ok(PageRankClient {
+            transport,
+            endpoint,
+            headers,
+            timeout: self.timeout,
+            concurrency: self.concurrency,
+            retry: self.retry,
+            limiter: self.rate_limit.map(|x| Arc::new(RateLimiter::new(x))),
+            normalize: self.normalize,
+            cache,
+        })
+    }
+}
+
+impl PageRankClient {
+    pub fn new(key: &str) -> Result<Self> {
+        Self::builder(key).build()
+    }
+
+    pub fn builder(key: &str) -> PageRankClientBuilder {
+        PageRankClientBuilder::new(key)
+    }
+
+    // one request, repeated inputs are queried once and the result has a Response per input.
+    // more than MAX_DOMAINS_PER_REQUEST unique domains fail with Error::Config, see rank_all
+    pub async fn rank<I, T>(&self, domains: I) -> Result<PageRank>
+    where I: IntoIterator<Item = T>, T: AsRef<str>
+    {
+        let plan = Plan::new(domains, &self.normalize);
+        let rank = self.fetch(plan.single_request()?).await?;
+        Ok(plan.fan_out(rank))
+    }
+
+    // already normalized and unique domains, cached ones are not requested again
+    async fn fetch(&self, domains: &[String]) -> Result<PageRank> {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.fetch_remote(domains).await,
+        };
+        let (cached, misses) = off_runtime(cache, {
+            let domains = domains.to_vec();
+            move |cache| cache.lookup(&domains)
+        }).await?;
+        let rank = self.fetch_remote(&misses).await?;
+        off_runtime(cache, move |cache| cache.complete(cached, &misses, rank)).await
+    }
+
+    // retried and rate limited
+    async fn fetch_remote(&self, domains: &[String]) -> Result<PageRank> {
+        if domains.is_empty() {
+            return Ok(empty())
+        }
+
+        let mut attempts = Attempts::new(&self.retry, self.limiter.as_deref());
+        loop {
+            while let Some(wait) = attempts.throttle()? {
+                tokio::time::sleep(wait).await;
+            }
+            match self.send(domains).await {
+                Ok(rank) => return Ok(rank),
+                Err(error) => tokio::time::sleep(attempts.failed(error)?).await,
+            }
+        }
+    }
+
+    async fn send(&self, domains: &[String]) -> Result<PageRank> {
+        read(self.transport.send(request(&self.endpoint, &self.headers, domains, self.timeout)).await?)
+    }
+
+    // None without a configured rate limit
+    pub fn remaining_budget(&self) -> Option<Budget> {
+        self.limiter.as_ref().map(|x| x.remaining())
+    }
+
+    // None without a configured cache
+    pub fn cache_stats(&self) -> Result<Option<CacheStats>> {
+        self.cache.as_ref().map(|x| x.stats()).transpose()
+    }
+
+    pub async fn rank_one<T>(&self, domain: T) -> Result<PageRankFirst>
+    where T: AsRef<str>
+    {
+        self.rank(Some(domain)).await?.try_into()
+    }
+
+    #[cfg(feature = "url")]
+    pub async fn rank_url(&self, url: &url::Url) -> Result<PageRankFirst> {
+        self.rank_one(crate::normalize::url_host(url)).await
+    }
+
+    // ranks the host of every url, responses stay in input order
+    #[cfg(feature = "url")]
+    pub async fn rank_urls<'u, I>(&self, urls: I) -> Result<PageRank>
+    where I: IntoIterator<Item = &'u url::Url>
+    {
+        self.rank_all(urls.into_iter().map(crate::normalize::url_host)).await
+    }
+
+    // splits the unique domains into api sized chunks and fans the responses back out in input order
+    pub async fn rank_all<I, T>(&self, domains: I) -> Result<PageRank>
+    where I: IntoIterator<Item = T>, T: AsRef<str>
+    {
+        let plan = Plan::new(domains, &self.normalize);
+
+        let ranks = stream::iter(plan.unique().chunks(MAX_DOMAINS_PER_REQUEST))
+            .map(|chunk| self.fetch(chunk))
+            .buffered(self.concurrency)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        Ok(plan.fan_out(merge(ranks)))
+    }
+
+    // yields responses in input order as each chunk completes. at most `concurrency` chunks are
+    // in flight and `domains` is only pulled as the consumer catches up. a failed chunk yields
+    // a single Err in its place and the stream carries on with the next one.
+    pub fn rank_stream<'a, S, T>(&'a self, domains: S) -> impl Stream<Item = Result<Response>> + 'a
+    where S: Stream<Item = T> + 'a, T: AsRef<str> + 'a
+    {
+        domains
+            .map(|x| x.as_ref().to_string())
+            .chunks(MAX_DOMAINS_PER_REQUEST)
+            .map(move |chunk| self.rank(chunk))
+            .buffered(self.concurrency)
+            .flat_map(|rank| stream::iter(match rank {
+                Ok(rank) => rank.response.into_iter().map(Ok).collect::<Vec<_>>(),
+                Err(e) => vec![Err(e)],
+            }))
+    }
+
+    pub fn rank_iter<'a, I, T>(&'a self, domains: I) -> impl Stream<Item = Result<Response>> + 'a
+    where I: IntoIterator<Item = T>, I::IntoIter: 'a, T: AsRef<str> + 'a
+    {
+        self.rank_stream(stream::iter(domains))
+    }
+
+    // one request per batch, all sharing the same connection pool. batches are limited like rank()
+    pub async fn rank_batch<I, D, T>(&self, batches: I) -> Result<Vec<PageRank>>
+    where I: IntoIterator<Item = D>, D: IntoIterator<Item = T>, T: AsRef<str>
+    {
+        let mut ranks = Vec::new();
+        for batch in batches {
+            ranks.push(self.rank(batch).await?);
+        }
+        Ok(ranks)
+    }
+}
+
+pub(crate) fn request(endpoint: &Url, headers: &HeaderMap, domains: &[String], timeout: Duration) -> HttpRequest {
+    let mut url = endpoint.clone();
+    url.query_pairs_mut().extend_pairs(domains.iter().map(|x| ("domains[]", x)));
+    HttpRequest { url, headers: headers.clone(), timeout }
+}
+
+// Cache calls are synchronous and may block on network io, they run on tokio's blocking pool
+// so a slow backend doesn't stall the runtime worker
+async fn off_runtime<F, R>(cache: &Arc<CacheLayer>, f: F) -> Result<R>
+where F: FnOnce(&CacheLayer) -> Result<R> + Send + 'static, R: Send + 'static
+{
+    let cache = cache.clone();
+    match tokio::task::spawn_blocking(move || f(&cache)).await {
+        Ok(result) => result,
+        Err(e) => match e.try_into_panic() {
+            Ok(panic) => std::panic::resume_unwind(panic),
+            Err(e) => Err(Error::Cache(e.to_string())),
+        },
+    }
+}
+
+pub(crate) fn headers(key: &str) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    headers.insert("API-OPR", HeaderValue::from_str(key).map_err(|_| Error::InvalidKey)?);
+    Ok(headers)
+}
+
+pub(crate) fn empty() -> PageRank {
+    PageRank::new(200, Vec::new(), String::new())
+}
+
+pub(crate) fn merge(ranks: Vec<PageRank>) -> PageRank {
+    let mut ranks = ranks.into_iter();
+    let mut merged = match ranks.next() {
+        Some(first) => first,
+        None => return empty(),
+    };
+    for rank in ranks {
+        merged.response.extend(rank.response);
+    }
+    merged
+}
+
+pub(crate) fn read(response: HttpResponse) -> Result<PageRank> {
+    decode(response.status, retry_after(&response.headers), &response.body)
+}
+
+// the api reports some failures in a 200 body, e.g. {"status_code":401,"error":"..."}
+#[derive(Deserialize)]
+struct Envelope {
+    status_code: u16,
+}
+
+pub(crate) fn decode(status: u16, retry_after: Option<Duration>, body: &str) -> Result<PageRank> {
+    let code = match status {
+        200..=299 => serde_json::from_str::<Envelope>(body).map(|x| x.status_code).unwrap_or(status),
+        _ => status,
+    };
+
+    match code {
+        200..=299 => serde_json::from_str(body).map_err(|source| Error::Decode { source, body: body.to_string() }),
+        401 | 403 => Err(Error::InvalidKey),
+        429 => Err(Error::RateLimited { retry_after }),
+        _ => Err(Error::Status { code, body: body.to_string() }),
+    }
+}
+
+// only the delay-seconds form, http dates are rare enough to ignore
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers.get(RETRY_AFTER)?
+        .to_str().ok()?
+        .trim()
+        .parse::<u64>().ok()
+        .map(Duration::from_secs)
+}
+
+// accepts roots with or without trailing slash, e.g. http://localhost:8080 or https://proxy/api/v1.0/
+pub(crate) fn endpoint(base_url: &str) -> Result<Url> {
+    let invalid = || Error::Config(format!("invalid api root: {}", base_url));
+
+    let mut url = Url::parse(base_url).map_err(|_| invalid())?;
+    if url.cannot_be_a_base() {
+        return Err(invalid())
+    }
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    url.join(ENDPOINT).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, aw, MockServer};
+
+    #[test]
+    fn test_endpoint() {
+        assert_eq!(endpoint(API_ROOT).unwrap().as_str(), "https://openpagerank.com/api/v1.0/getPageRank");
+        assert_eq!(endpoint("http://localhost:8080").unwrap().as_str(), "http://localhost:8080/getPageRank");
+        assert_eq!(endpoint("https://proxy.local/opr/api/v1.0/").unwrap().as_str(), "https://proxy.local/opr/api/v1.0/getPageRank");
+        assert!(endpoint("not a url").is_err());
+        assert!(endpoint("mailto:someone@example.com").is_err());
+    }
+
+    #[test]
+    fn test_query() {
+        let client = PageRankClient::builder("key").base_url("http://localhost:8080").build().unwrap();
+        let request = request(&client.endpoint, &client.headers, &["example.com".to_string()], client.timeout);
+        assert_eq!(request.url.as_str(), "http://localhost:8080/getPageRank?domains%5B%5D=example.com");
+    }
+
+    #[test]
+    fn test_rank_all_chunks() {
+        let server = MockServer::start(testing::echo);
+        let client = PageRankClient::builder("key").base_url(server.url()).concurrency(4).build().unwrap();
+        let domains = (0..250).map(|i| format!("domain{}.com", i)).collect::<Vec<_>>();
+
+        let rank = aw!(client.rank_all(&domains)).unwrap();
+        let sizes = server.requests().iter().map(|r| r.domains.len()).collect::<Vec<_>>();
+        assert_eq!(sizes.len(), 3);
+        assert!(sizes.iter().all(|n| *n <= MAX_DOMAINS_PER_REQUEST));
+        assert_eq!(rank.response.iter().map(|r| r.domain.clone()).collect::<Vec<_>>(), domains);
+    }
+
+    #[test]
+    fn test_rank_all_empty() {
+        let server = MockServer::start(testing::echo);
+        let client = PageRankClient::builder("key").base_url(server.url()).build().unwrap();
+        let rank = aw!(client.rank_all(Vec::<String>::new())).unwrap();
+        assert!(rank.response.is_empty());
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn test_rank_stream() {
+        let server = MockServer::start(|r| match r.domains.iter().any(|d| d == "bad.com") {
+            true => testing::Reply::status(500, String::new()),
+            false => testing::echo(r),
+        });
+        let client = PageRankClient::builder("key").base_url(server.url()).concurrency(2).build().unwrap();
+
+        let mut domains = (0..250).map(|i| format!("domain{}.com", i)).collect::<Vec<_>>();
+        domains[150] = "bad.com".to_string();
+
+        let results = aw!(client.rank_iter(&domains).collect::<Vec<_>>());
+        assert_eq!(results.len(), 151);
+        assert!(results[..100].iter().all(|r| r.is_ok()));
+        assert!(matches!(results[100], Err(Error::Status { code: 500, .. })));
+        assert_eq!(results[150].as_ref().unwrap().domain, "domain249.com");
+    }
+
+    #[test]
+    fn test_normalizes_input() {
+        let server = MockServer::start(testing::echo);
+        let client = PageRankClient::builder("key")
+            .base_url(server.url())
+            .normalize(NormalizeOptions::new().strip_www(true))
+            .build()
+            .unwrap();
+
+        let rank = aw!(client.rank(vec!["https://WWW.Example.com/path", "example.org."])).unwrap();
+        assert_eq!(server.requests()[0].domains, vec!["example.com", "example.org"]);
+        assert_eq!(rank.response.len(), 2);
+    }
+
+    #[test]
+    fn test_dedup_and_fan_out() {
+        let server = MockServer::start(testing::echo);
+        let client = PageRankClient::builder("key").base_url(server.url()).build().unwrap();
+        let inputs = vec!["a.com", "https://A.com/", "b.com", "not a domain", "a.com"];
+
+        let rank = aw!(client.rank_all(&inputs)).unwrap();
+        assert_eq!(server.requests()[0].domains, vec!["a.com", "b.com"]);
+        assert_eq!(rank.response.len(), inputs.len());
+        assert_eq!(rank.response[1].domain, "a.com");
+        assert_eq!(rank.response[3].result(), crate::DomainResult::Invalid);
+
+        let rank = aw!(client.rank(vec!["not a domain"])).unwrap();
+        assert_eq!(rank.response[0].result(), crate::DomainResult::Invalid);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn test_rank_limit() {
+        let server = MockServer::start(testing::echo);
+        let client = PageRankClient::builder("key").base_url(server.url()).build().unwrap();
+
+        let domains = (0..150).map(|i| format!("domain{}.com", i % 100)).collect::<Vec<_>>();
+        assert_eq!(aw!(client.rank(&domains)).unwrap().response.len(), 150);
+
+        let domains = (0..150).map(|i| format!("domain{}.com", i)).collect::<Vec<_>>();
+        assert!(matches!(aw!(client.rank(&domains)), Err(Error::Config(_))));
+        assert!(matches!(aw!(client.rank_batch(vec![&domains[..]])), Err(Error::Config(_))));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[cfg(feature = "url")]
+    #[test]
+    fn test_rank_url() {
+        let server = MockServer::start(testing::echo);
+        let client = PageRankClient::builder("key").base_url(server.url()).build().unwrap();
+
+        let first = aw!(client.rank_url(&url::Url::parse("https://www.Example.com/path").unwrap())).unwrap();
+        assert_eq!(first.response.domain, "www.example.com");
+        assert!(first.is_found());
+
+        let urls = ["https://a.com/x", "mailto:someone@b.com", "https://bücher.de"]
+            .iter()
+            .map(|x| url::Url::parse(x).unwrap())
+            .collect::<Vec<_>>();
+        let rank = aw!(client.rank_urls(&urls)).unwrap();
+        assert_eq!(rank.response[0].domain, "a.com");
+        assert_eq!(rank.response[1].result(), crate::DomainResult::Invalid);
+        assert_eq!(rank.response[2].domain, "xn--bcher-kva.de");
+        assert_eq!(server.requests()[1].domains, ["a.com", "xn--bcher-kva.de"]);
+    }
+
+    #[test]
+    fn test_decode() {
+        let body = testing::body(vec![testing::found("example.com")]);
+        assert_eq!(decode(200, None, &body).unwrap().response[0].domain, "example.com");
+
+        assert!(matches!(decode(401, None, ""), Err(Error::InvalidKey)));
+        assert!(matches!(decode(200, None, r#"{"status_code":403,"error":"Invalid API key"}"#), Err(Error::InvalidKey)));
+        assert!(matches!(decode(500, None, "oops"), Err(Error::Status { code: 500, .. })));
+        match decode(200, None, "<html>") {
+            Err(Error::Decode { body, .. }) => assert_eq!(body, "<html>"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rate_limited() {
+        let server = MockServer::start(|_| testing::rate_limited(7));
+        let client = PageRankClient::builder("key").base_url(server.url()).build().unwrap();
+        match aw!(client.rank(vec!["example.com"])) {
+            Err(Error::RateLimited { retry_after }) => assert_eq!(retry_after, Some(Duration::from_secs(7))),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_timeout() {
+        let server = MockServer::start(testing::slow(Duration::from_millis(500), testing::echo));
+        let client = PageRankClient::builder("key").base_url(server.url()).timeout(Duration::from_millis(50)).build().unwrap();
+        assert!(matches!(aw!(client.rank(vec!["example.com"])), Err(Error::Timeout)));
+    }
+}
@@ -0,0 +1,50 @@
+use chrono::NaiveDate;
+
+use crate::{PageRank, PageRankFirst};
+
+// the api formats last_updated like "4th Jan 2024", iso dates are accepted as a fallback
+pub(crate) fn parse_last_updated(s: &str) -> Option<NaiveDate> {
+    let s = s.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Some(date)
+    }
+
+    let mut parts = s.split_whitespace();
+    let (day, month, year) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() {
+        return None
+    }
+
+    let day = day.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    NaiveDate::parse_from_str(&format!("{} {} {}", day, month, year), "%d %B %Y").ok()
+}
+
+impl PageRank {
+    // None when the payload uses a format we don't know, last_updated keeps the raw string
+    pub fn last_updated_date(&self) -> Option<NaiveDate> {
+        parse_last_updated(&self.last_updated)
+    }
+}
+
+impl PageRankFirst {
+    pub fn last_updated_date(&self) -> Option<NaiveDate> {
+        parse_last_updated(&self.last_updated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_last_updated() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 4).unwrap();
+        assert_eq!(parse_last_updated("4th Jan 2024"), Some(date));
+        assert_eq!(parse_last_updated("4 January 2024"), Some(date));
+        assert_eq!(parse_last_updated("2024-01-04"), Some(date));
+        assert_eq!(parse_last_updated("21st Mar 2023"), NaiveDate::from_ymd_opt(2023, 3, 21));
+        assert_eq!(parse_last_updated("32nd Jan 2024"), None);
+        assert_eq!(parse_last_updated("yesterday"), None);
+        assert_eq!(parse_last_updated(""), None);
+    }
+}
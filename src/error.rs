@@ -0,0 +1,82 @@
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    // api rejected the API-OPR key (401/403), or the key is not a valid header value
+    InvalidKey,
+    // api answered 429, retry_after comes from the Retry-After header when present
+    RateLimited { retry_after: Option<Duration> },
+    Timeout,
+    // any other non-success answer from the api
+    Status { code: u16, body: String },
+    Transport(reqwest::Error),
+    // body could not be read as PageRank, the raw body is kept for debugging
+    Decode { source: serde_json::Error, body: String },
+    EmptyResponse,
+    // a single domain result was expected, converting would drop the others
+    MultipleResponses(usize),
+    InvalidDomain(String),
+    // offline mode and these domains are not in the cache
+    NotCached(Vec<String>),
+    // the cache backend failed or answered something unexpected
+    Cache(String),
+    // fixture file unreadable, or no recorded exchange for a replayed request
+    Fixture(String),
+    Tls(String),
+    Config(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidKey => write!(f, "invalid api key"),
+            Error::RateLimited { retry_after: Some(after) } => write!(f, "rate limited, retry after {}s", after.as_secs()),
+            Error::RateLimited { retry_after: None } => write!(f, "rate limited"),
+            Error::Timeout => write!(f, "request timed out"),
+            Error::Status { code, .. } => write!(f, "unexpected status code {}", code),
+            Error::Transport(e) => write!(f, "transport error: {}", e),
+            Error::Decode { source, .. } => write!(f, "failed to decode response: {}", source),
+            Error::EmptyResponse => write!(f, "no response found"),
+            Error::MultipleResponses(n) => write!(f, "expected a single response, got {}", n),
+            Error::InvalidDomain(domain) => write!(f, "invalid domain: {}", domain),
+            Error::NotCached(domains) => write!(f, "offline and not cached: {}", domains.join(", ")),
+            Error::Cache(msg) => write!(f, "cache error: {}", msg),
+            Error::Fixture(msg) => write!(f, "fixture error: {}", msg),
+            Error::Tls(msg) => write!(f, "tls error: {}", msg),
+            Error::Config(msg) => write!(f, "invalid configuration: {}", msg),
+            Error::Io(e) => write!(f, "io error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Transport(e) => Some(e),
+            Error::Decode { source, .. } => Some(source),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        match e.is_timeout() {
+            true => Error::Timeout,
+            false => Error::Transport(e),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
@@ -0,0 +1,309 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use futures::future::BoxFuture;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::transport::{HttpRequest, HttpResponse, HttpTransport};
+use crate::{Error, Result};
+
+const REDACTED: &str = "[redacted]";
+
+// recorded api exchanges, one json line each, so code built on the client can be tested
+// against real answers without a key or network. the API-OPR key is never written.
+// recording only appends, a line cut short by a crash is skipped on the next load.
+#[derive(Debug)]
+pub(crate) struct Fixtures {
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+enum State {
+    Record(File),
+    Replay {
+        exchanges: Vec<Exchange>,
+        // each exchange is served once before the last match repeats
+        used: Vec<bool>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+struct Exchange {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+struct RecordedRequest {
+    url: String,
+    domains: Vec<String>,
+    headers: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+struct RecordedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Fixtures {
+    // appends to the file when it already holds exchanges
+    pub(crate) fn record(path: &Path) -> Result<Self> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        parse(path, &content)?;
+
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if !content.is_empty() && !content.ends_with('\n') {
+            let tail = content.rfind('\n').map_or(0, |i| i + 1);
+            match serde_json::from_str::<Exchange>(&content[tail..]) {
+                Ok(_) => file.write_all(b"\n")?,
+                // drops the line cut short, so new lines don't land after it
+                Err(_) => file.set_len(tail as u64)?,
+            }
+        }
+        Ok(Self { state: Mutex::new(State::Record(file)) })
+    }
+
+    pub(crate) fn replay(path: &Path) -> Result<Self> {
+        let exchanges = parse(path, &fs::read_to_string(path)?)?;
+        let used = vec![false; exchanges.len()];
+        Ok(Self { state: Mutex::new(State::Replay { exchanges, used }) })
+    }
+
+    pub(crate) fn is_replay(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), State::Replay { .. })
+    }
+
+    // the recorded answer for exactly these domains, in recording order
+    pub(crate) fn find(&self, request: &HttpRequest) -> Result<HttpResponse> {
+        let domains = request.domains();
+        let mut state = self.state.lock().unwrap();
+        let State::Replay { exchanges, used } = &mut *state else {
+            return Err(Error::Fixture("not replaying".to_string()))
+        };
+        let matches = exchanges.iter()
+            .enumerate()
+            .filter(|(_, x)| x.request.domains == domains)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+
+        let i = matches.iter()
+            .find(|i| !used[**i])
+            .or(matches.last())
+            .copied()
+            .ok_or_else(|| Error::Fixture(format!("no recorded exchange for {}", domains.join(", "))))?;
+        used[i] = true;
+
+        let recorded = &exchanges[i].response;
+        Ok(HttpResponse { status: recorded.status, headers: header_map(&recorded.headers), body: recorded.body.clone() })
+    }
+
+    pub(crate) fn save(&self, request: &HttpRequest, response: &HttpResponse) -> Result<()> {
+        let exchange = Exchange {
+            request: RecordedRequest {
+                url: request.url.to_string(),
+                domains: request.domains(),
+                headers: redact(&request.headers),
+            },
+            response: RecordedResponse {
+                status: response.status,
+                headers: pairs(&response.headers),
+                body: response.body.clone(),
+            },
+        };
+
+        let mut line = serde_json::to_vec(&exchange).map_err(io::Error::from)?;
+        line.push(b'\n');
+
+        match &mut *self.state.lock().unwrap() {
+            State::Record(file) => Ok(file.write_all(&line)?),
+            State::Replay { .. } => Err(Error::Fixture("not recording".to_string())),
+        }
+    }
+}
+
+// records every exchange of `inner`, failed ones included. T is the async or blocking transport
+#[derive(Debug)]
+pub(crate) struct Recorder<T: ?Sized> {
+    inner: Arc<T>,
+    fixtures: Fixtures,
+}
+
+impl<T: ?Sized> Recorder<T> {
+    pub(crate) fn new(inner: Arc<T>, fixtures: Fixtures) -> Self {
+        Self { inner, fixtures }
+    }
+}
+
+impl HttpTransport for Recorder<dyn HttpTransport> {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse>> {
+        Box::pin(async move {
+            let response = self.inner.send(request.clone()).await?;
+            self.fixtures.save(&request, &response)?;
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl crate::blocking::HttpTransport for Recorder<dyn crate::blocking::HttpTransport> {
+    fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+        let response = crate::blocking::HttpTransport::send(&*self.inner, request.clone())?;
+        self.fixtures.save(&request, &response)?;
+        Ok(response)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Replayer {
+    fixtures: Fixtures,
+}
+
+impl Replayer {
+    pub(crate) fn new(fixtures: Fixtures) -> Self {
+        Self { fixtures }
+    }
+}
+
+impl HttpTransport for Replayer {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse>> {
+        let response = self.fixtures.find(&request);
+        Box::pin(async move { response })
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl crate::blocking::HttpTransport for Replayer {
+    fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+        self.fixtures.find(&request)
+    }
+}
+
+// the api key is replaced but kept, so fixtures show it was sent
+fn redact(headers: &HeaderMap) -> Vec<(String, String)> {
+    pairs(headers).into_iter()
+        .map(|(k, v)| match k.eq_ignore_ascii_case("api-opr") {
+            true => (k, REDACTED.to_string()),
+            false => (k, v),
+        })
+        .collect()
+}
+
+fn pairs(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers.iter()
+        .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v.as_bytes()).to_string()))
+        .collect()
+}
+
+fn header_map(pairs: &[(String, String)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (k, v) in pairs {
+        if let (Ok(k), Ok(v)) = (HeaderName::try_from(k.as_str()), HeaderValue::from_str(v)) {
+            headers.append(k, v);
+        }
+    }
+    headers
+}
+
+// a broken last line without a newline is a write cut short and skipped, anything else fails
+fn parse(path: &Path, content: &str) -> Result<Vec<Exchange>> {
+    let lines = content.lines().collect::<Vec<_>>();
+    let mut exchanges = Vec::with_capacity(lines.len());
+    for (n, line) in lines.iter().enumerate().filter(|(_, x)| !x.trim().is_empty()) {
+        match serde_json::from_str(line) {
+            Ok(exchange) => exchanges.push(exchange),
+            Err(_) if n + 1 == lines.len() && !content.ends_with('\n') => {}
+            Err(e) => return Err(Error::Fixture(format!("{}:{}: {}", path.display(), n + 1, e))),
+        }
+    }
+    Ok(exchanges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::testing::{self, aw, MockServer};
+    use crate::{DomainResult, PageRankClient};
+
+    #[test]
+    fn test_record_replay() {
+        let path = testing::temp_path("fixtures.jsonl");
+        let server = MockServer::start(testing::known(&[("example.com", 1234, 4.5)]));
+        let recorder = PageRankClient::builder("secret-key").base_url(server.url()).record(&path).build().unwrap();
+        let recorded = aw!(recorder.rank(vec!["example.com", "missing.com"])).unwrap();
+        aw!(recorder.rank(vec!["example.com"])).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("secret-key"));
+        assert!(content.contains(REDACTED));
+
+        let replayer = PageRankClient::builder("other-key").base_url("http://127.0.0.1:9").replay(&path).build().unwrap();
+        let replayed = aw!(replayer.rank(vec!["example.com", "missing.com"])).unwrap();
+        assert_eq!(replayed, recorded);
+        assert_eq!(replayed.response[1].result(), DomainResult::NotFound);
+        assert_eq!(aw!(replayer.rank_one("https://example.com/")).unwrap().response.rank, Some(1234));
+        assert!(matches!(aw!(replayer.rank(vec!["other.com"])), Err(Error::Fixture(_))));
+        assert_eq!(server.requests().len(), 2);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_replay_in_order() {
+        let path = testing::temp_path("fixtures-order.jsonl");
+        let server = MockServer::start({
+            let calls = Mutex::new(0);
+            move |r| {
+                let mut calls = calls.lock().unwrap();
+                *calls += 1;
+                match *calls {
+                    1 => testing::rate_limited(3),
+                    _ => testing::echo(r),
+                }
+            }
+        });
+        let recorder = PageRankClient::builder("key").base_url(server.url()).record(&path).build().unwrap();
+        assert!(aw!(recorder.rank(vec!["a.com"])).is_err());
+        assert!(aw!(recorder.rank(vec!["a.com"])).is_ok());
+
+        let replayer = PageRankClient::builder("key").replay(&path).build().unwrap();
+        match aw!(replayer.rank(vec!["a.com"])) {
+            Err(Error::RateLimited { retry_after }) => assert_eq!(retry_after, Some(Duration::from_secs(3))),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(aw!(replayer.rank(vec!["a.com"])).is_ok());
+        assert!(aw!(replayer.rank(vec!["a.com"])).is_ok());
+
+        assert!(PageRankClient::builder("key").replay("/nonexistent/fixtures.json").build().is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_interrupted_recording() {
+        let path = testing::temp_path("fixtures-interrupted.jsonl");
+        let server = MockServer::start(testing::echo);
+        let recorder = PageRankClient::builder("key").base_url(server.url()).record(&path).build().unwrap();
+        aw!(recorder.rank(vec!["a.com"])).unwrap();
+
+        // killed half way through the next line
+        let content = fs::read_to_string(&path).unwrap();
+        fs::write(&path, format!("{}{}", content, &content[..30])).unwrap();
+        let recorder = PageRankClient::builder("key").base_url(server.url()).record(&path).build().unwrap();
+        aw!(recorder.rank(vec!["b.com"])).unwrap();
+
+        let replayer = PageRankClient::builder("key").replay(&path).build().unwrap();
+        assert!(aw!(replayer.rank(vec!["a.com"])).is_ok());
+        assert!(aw!(replayer.rank(vec!["b.com"])).is_ok());
+
+        fs::write(&path, format!("{{\n{}", content)).unwrap();
+        assert!(PageRankClient::builder("key").replay(&path).build().is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -2,11 +2,42 @@
 extern crate serde;
 
 use std::time::Duration;
-use anyhow::Result;
-use reqwest::Client;
-use reqwest::header::{HeaderMap, HeaderValue};
 
-const API_ROOT: &'static str = "https://openpagerank.com/api/v1.0/getPageRank";
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod cache;
+mod client;
+#[cfg(feature = "chrono")]
+mod date;
+mod error;
+mod fixture;
+mod limit;
+mod lookup;
+pub mod normalize;
+mod plan;
+mod rank;
+#[cfg(feature = "psl")]
+pub mod registrable;
+mod retry;
+mod status;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod tls;
+mod transport;
+
+pub use cache::{Cache, CacheEntry, CacheStats, FileCache, MemoryCache};
+#[cfg(feature = "redis")]
+pub use cache::RedisCache;
+pub use client::{PageRankClient, PageRankClientBuilder, MAX_DOMAINS_PER_REQUEST};
+pub use error::{Error, Result};
+pub use limit::{Budget, RateLimit};
+pub use lookup::Lookup;
+pub use normalize::NormalizeOptions;
+#[cfg(feature = "psl")]
+pub use registrable::SuffixList;
+pub use retry::RetryPolicy;
+pub use status::DomainResult;
+pub use transport::{HttpRequest, HttpResponse, HttpTransport, ReqwestTransport};
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 pub struct Response {
@@ -14,7 +45,8 @@ pub struct Response {
     pub error: String,
     pub page_rank_integer: u32,
     pub page_rank_decimal: f32,
-    pub rank: Option<String>,
+    #[serde(default, with = "rank")]
+    pub rank: Option<u64>,
     pub domain: String
 }
 
@@ -22,7 +54,7 @@ pub struct Response {
 pub struct PageRank {
     pub status_code: u16,
     pub response: Vec<Response>,
-    pub last_updated: String
+    pub last_updated: String,
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
@@ -32,55 +64,37 @@ pub struct PageRankFirst {
     pub last_updated: String
 }
 
-// used in url feature
-impl TryInto<PageRankFirst> for PageRank {
-    type Error = anyhow::Error;
-
-    fn try_into(self) -> Result<PageRankFirst, Self::Error> {
-        match self.response.get(0) {
-            Some(response) => Ok(PageRankFirst {
-                status_code: self.status_code,
-                response: response.clone(),
-                last_updated: self.last_updated,
+// exactly one response, anything else would silently drop data
+impl TryFrom<PageRank> for PageRankFirst {
+    type Error = Error;
+
+    fn try_from(rank: PageRank) -> Result<Self> {
+        let len = rank.response.len();
+        let mut responses = rank.response.into_iter();
+        match (responses.next(), len) {
+            (Some(response), 1) => Ok(PageRankFirst {
+                status_code: rank.status_code,
+                response,
+                last_updated: rank.last_updated,
             }),
-            None => return Err(anyhow::anyhow!("no response found"))
+            (None, _) => Err(Error::EmptyResponse),
+            (Some(_), n) => Err(Error::MultipleResponses(n)),
         }
     }
-} 
+}
+
+impl From<PageRankFirst> for PageRank {
+    fn from(first: PageRankFirst) -> Self {
+        PageRank::new(first.status_code, vec![first.response], first.last_updated)
+    }
+}
 
 impl PageRank {
+    // builds a throwaway client, prefer PageRankClient when ranking more than once
     pub async fn rank<T> (domains: Vec<T>, key: &str, timeout: Duration) -> Result<Self> 
     where T: AsRef<str>
     {
-        let mut headers = HeaderMap::new();
-        headers.insert("API-OPR", HeaderValue::from_str(key)?);
-
-        let client = Client::builder()
-            .default_headers(headers)
-            .danger_accept_invalid_certs(true)
-            .build()?;
-
-        let domains = domains.into_iter().map(|x| Self::remove_trailing_slash(x.as_ref())).collect::<Vec<_>>();
-        let query = domains.into_iter().map(|x| ("domains[]", x)).collect::<Vec<_>>();
-
-        let rank = client.get(API_ROOT)
-            .query(&query)
-            .timeout(timeout)
-            .send()
-            .await?
-            .json::<Self>()
-            .await?;
-
-        Ok(rank)
-    }
-
-    // open rank api need no trailing slash on url
-    fn remove_trailing_slash<T>(s: T) -> String
-    where T: ToString
-    {
-        let mut s = s.to_string();
-        while s.ends_with('/') { s.pop(); }
-        return s
+        rank_with(PageRankClient::builder(key), domains, timeout).await
     }
 
     pub fn status_code(&self) -> u16 {
@@ -91,43 +105,98 @@ impl PageRank {
         self.last_updated.clone()
     }
 
+    // clones every response, see responses() and get() for borrowing access
     pub fn response(&self) -> Vec<Response> {
         self.response.clone()
     }
 }
 
+// PageRank::rank on any builder, so tests can point it at a mock server
+async fn rank_with<T>(builder: PageRankClientBuilder, domains: Vec<T>, timeout: Duration) -> Result<PageRank>
+where T: AsRef<str>
+{
+    builder.timeout(timeout).build()?.rank_all(domains).await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::OnceLock;
+    use crate::testing::{self, aw, MockServer};
+
+    fn server() -> &'static MockServer {
+        static SERVER: OnceLock<MockServer> = OnceLock::new();
+        SERVER.get_or_init(|| MockServer::start(testing::known(&[("monitorapp.com", 1_254_335, 3.52), ("google.com", 1, 10.0)])))
+    }
 
-    macro_rules! aw {
-        ($e:expr) => {
-            tokio_test::block_on($e)
-        };
+    // PageRank::rank without its fixed api root
+    fn rank<T>(domains: Vec<T>) -> Result<PageRank>
+    where T: AsRef<str>
+    {
+        aw!(rank_with(PageRankClient::builder("key").base_url(server().url()), domains, Duration::from_secs(10)))
     }
 
     #[test]
     fn test_url() {
         let url = url::Url::parse("https://monitorapp.com").unwrap();
-        let rank = aw!(PageRank::rank(vec![url], &api_key(), Duration::from_secs(10))).unwrap();
-        assert!(rank.response[0].rank.is_some());
+        let rank = rank(vec![url]).unwrap();
+        assert_eq!(rank.response[0].rank, Some(1_254_335));
         assert_eq!(rank.response[0].domain, "monitorapp.com");
     }
 
     #[test]
     fn test_domain() {
-        let rank =aw!(PageRank::rank(vec!["monitorapp.com"], &api_key(), Duration::from_secs(10))).unwrap();
+        let rank = rank(vec!["monitorapp.com"]).unwrap();
         assert!(rank.response[0].rank.is_some());
+        assert_eq!(rank.response[0].page_rank_integer, 4);
         assert_eq!(rank.response[0].domain, "monitorapp.com");
+        assert_eq!(rank.last_updated, testing::LAST_UPDATED);
     }
 
     #[test]
     fn test_invalid_url() {
-        let rank =aw!(PageRank::rank(vec!["https://invalid-monitorapp.com"], &api_key(), Duration::from_secs(10))).unwrap();
+        let rank = rank(vec!["https://invalid-monitorapp.com"]).unwrap();
         assert_eq!(rank.response[0].rank, None);
+        assert_eq!(rank.response[0].result(), DomainResult::NotFound);
     }
 
-    fn api_key() -> String {
-        "kc8kgoc00oo00ggskksc00kgo0o4o04swkc0cs88".to_string()
+    #[test]
+    fn test_client_reuse() {
+        let client = PageRankClient::builder("key").base_url(server().url()).build().unwrap();
+        let first = aw!(client.rank_one("monitorapp.com")).unwrap();
+        let batches = aw!(client.rank_batch(vec![vec!["monitorapp.com"], vec!["google.com"]])).unwrap();
+        assert_eq!(first.response.domain, "monitorapp.com");
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[1].response[0].domain, "google.com");
+    }
+
+    #[test]
+    fn test_api_failures() {
+        let client = |server: &MockServer| PageRankClient::builder("key").base_url(server.url()).build().unwrap();
+
+        let server = MockServer::start(|_| testing::invalid_key());
+        assert!(matches!(aw!(client(&server).rank(vec!["monitorapp.com"])), Err(Error::InvalidKey)));
+
+        let server = MockServer::start(|_| testing::malformed());
+        assert!(matches!(aw!(client(&server).rank(vec!["monitorapp.com"])), Err(Error::Decode { .. })));
+
+        let server = MockServer::start(|_| testing::rate_limited(30));
+        assert!(matches!(aw!(client(&server).rank(vec!["monitorapp.com"])), Err(Error::RateLimited { .. })));
+        assert_eq!(server.requests()[0].header("API-OPR"), Some("key"));
+    }
+
+    #[test]
+    fn test_first_conversions() {
+        let rank: PageRank = serde_json::from_str(&testing::body(vec![testing::found("example.com")])).unwrap();
+        let first = PageRankFirst::try_from(rank.clone()).unwrap();
+        assert_eq!(first.response.domain, "example.com");
+        assert_eq!(first.last_updated, "4th Jan 2024");
+        assert_eq!(PageRank::from(first), rank);
+
+        let empty = PageRank::new(200, Vec::new(), String::new());
+        assert!(matches!(PageRankFirst::try_from(empty), Err(Error::EmptyResponse)));
+
+        let two: PageRank = serde_json::from_str(&testing::body(vec![testing::found("a.com"), testing::found("b.com")])).unwrap();
+        assert!(matches!(PageRankFirst::try_from(two), Err(Error::MultipleResponses(2))));
     }
 }
\ No newline at end of file
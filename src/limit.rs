@@ -0,0 +1,244 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{Error, Result};
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+// client side pacing, every http request (one chunk of up to 100 domains) costs one token.
+// buckets start full so short bursts up to the limit go out immediately.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimit {
+    per_second: Option<u32>,
+    per_minute: Option<u32>,
+    daily_quota: Option<u64>,
+    block: bool,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self { per_second: None, per_minute: None, daily_quota: None, block: true }
+    }
+}
+
+impl RateLimit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn per_second(mut self, requests: u32) -> Self {
+        self.per_second = Some(requests.max(1));
+        self
+    }
+
+    pub fn per_minute(mut self, requests: u32) -> Self {
+        self.per_minute = Some(requests.max(1));
+        self
+    }
+
+    // requests per rolling 24h window, counted from the first request
+    pub fn daily_quota(mut self, requests: u64) -> Self {
+        self.daily_quota = Some(requests);
+        self
+    }
+
+    // wait for a token (default) or fail fast with Error::RateLimited.
+    // an exhausted daily quota always fails instead of sleeping for hours.
+    pub fn block(mut self, block: bool) -> Self {
+        self.block = block;
+        self
+    }
+}
+
+// remaining requests in each window right now, None where no limit is configured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    pub per_second: Option<u32>,
+    pub per_minute: Option<u32>,
+    pub daily: Option<u64>,
+}
+
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    block: bool,
+    daily_quota: Option<u64>,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    second: Option<Bucket>,
+    minute: Option<Bucket>,
+    // start of the current daily window, set by the first request in it
+    day_start: Option<Instant>,
+    used_today: u64,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    tokens: f64,
+    rate: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(capacity: u32, per: Duration, now: Instant) -> Self {
+        let capacity = capacity as f64;
+        Self { capacity, tokens: capacity, rate: capacity / per.as_secs_f64(), last: now }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last = now;
+    }
+
+    fn wait(&self) -> Duration {
+        match self.tokens >= 1.0 {
+            true => Duration::ZERO,
+            false => Duration::from_secs_f64((1.0 - self.tokens) / self.rate),
+        }
+    }
+}
+
+impl State {
+    fn buckets(&mut self) -> impl Iterator<Item = &mut Bucket> {
+        [self.second.as_mut(), self.minute.as_mut()].into_iter().flatten()
+    }
+
+    fn roll_day(&mut self, now: Instant) {
+        if self.day_start.is_some_and(|start| now.saturating_duration_since(start) >= DAY) {
+            self.day_start = None;
+            self.used_today = 0;
+        }
+    }
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        let now = Instant::now();
+        Self {
+            block: limit.block,
+            daily_quota: limit.daily_quota,
+            state: Mutex::new(State {
+                second: limit.per_second.map(|n| Bucket::new(n, Duration::from_secs(1), now)),
+                minute: limit.per_minute.map(|n| Bucket::new(n, Duration::from_secs(60), now)),
+                day_start: None,
+                used_today: 0,
+            }),
+        }
+    }
+
+    // Ok(None) takes a token, Ok(Some(wait)) asks the caller to sleep and try again
+    pub(crate) fn acquire(&self) -> Result<Option<Duration>> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        state.roll_day(now);
+        if let Some(quota) = self.daily_quota {
+            if state.used_today >= quota {
+                let retry_after = match state.day_start {
+                    Some(start) => DAY.saturating_sub(now.saturating_duration_since(start)),
+                    None => DAY,
+                };
+                return Err(Error::RateLimited { retry_after: Some(retry_after) })
+            }
+        }
+
+        let wait = state.buckets()
+            .map(|bucket| { bucket.refill(now); bucket.wait() })
+            .max()
+            .unwrap_or(Duration::ZERO);
+
+        if !wait.is_zero() {
+            return match self.block {
+                true => Ok(Some(wait)),
+                false => Err(Error::RateLimited { retry_after: Some(wait) }),
+            }
+        }
+
+        state.buckets().for_each(|bucket| bucket.tokens -= 1.0);
+        state.day_start.get_or_insert(now);
+        state.used_today += 1;
+        Ok(None)
+    }
+
+    pub(crate) fn remaining(&self) -> Budget {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        state.roll_day(now);
+        state.buckets().for_each(|bucket| bucket.refill(now));
+
+        Budget {
+            per_second: state.second.as_ref().map(|b| b.tokens.floor() as u32),
+            per_minute: state.minute.as_ref().map(|b| b.tokens.floor() as u32),
+            daily: self.daily_quota.map(|quota| quota.saturating_sub(state.used_today)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, aw, MockServer};
+    use crate::PageRankClient;
+
+    #[test]
+    fn test_fail_fast() {
+        let limiter = RateLimiter::new(RateLimit::new().per_second(2).block(false));
+        assert!(limiter.acquire().unwrap().is_none());
+        assert!(limiter.acquire().unwrap().is_none());
+        assert!(matches!(limiter.acquire(), Err(Error::RateLimited { retry_after: Some(_) })));
+    }
+
+    #[test]
+    fn test_wait() {
+        let limiter = RateLimiter::new(RateLimit::new().per_minute(1));
+        assert!(limiter.acquire().unwrap().is_none());
+        let wait = limiter.acquire().unwrap().unwrap();
+        assert!(wait > Duration::from_secs(50) && wait <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_daily_quota() {
+        // a per minute bucket refills slowly enough to read back exactly
+        let limiter = RateLimiter::new(RateLimit::new().per_minute(100).daily_quota(3));
+        // the window opens with the first request, not when the limiter is built
+        assert!(limiter.state.lock().unwrap().day_start.is_none());
+        for _ in 0..3 {
+            assert!(limiter.acquire().unwrap().is_none());
+        }
+        match limiter.acquire() {
+            Err(Error::RateLimited { retry_after: Some(after) }) => assert!(after > DAY - Duration::from_secs(60)),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(limiter.remaining(), Budget { per_second: None, per_minute: Some(97), daily: Some(0) });
+
+        // a day later the quota is back
+        if let Some(start) = Instant::now().checked_sub(DAY) {
+            limiter.state.lock().unwrap().day_start = Some(start);
+            assert!(limiter.acquire().unwrap().is_none());
+            assert_eq!(limiter.remaining().daily, Some(2));
+        }
+    }
+
+    #[test]
+    fn test_shared_by_batches() {
+        let server = MockServer::start(testing::echo);
+        let client = PageRankClient::builder("key")
+            .base_url(server.url())
+            .concurrency(4)
+            .rate_limit(RateLimit::new().per_second(2))
+            .build()
+            .unwrap();
+        let domains = (0..300).map(|i| format!("domain{}.com", i)).collect::<Vec<_>>();
+
+        let start = Instant::now();
+        let rank = aw!(client.rank_all(&domains)).unwrap();
+        assert_eq!(rank.response.len(), 300);
+        assert!(start.elapsed() >= Duration::from_millis(400));
+        assert_eq!(client.remaining_budget().unwrap().per_second, Some(0));
+    }
+}
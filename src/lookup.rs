@@ -0,0 +1,202 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::normalize::{normalize, NormalizeOptions};
+use crate::{PageRank, Response};
+
+impl PageRank {
+    pub fn new(status_code: u16, response: Vec<Response>, last_updated: String) -> Self {
+        Self { status_code, response, last_updated }
+    }
+
+    // accepts anything normalize does with the default options, "https://Example.com/" finds
+    // "example.com". a linear scan, see lookup() for repeated lookups
+    pub fn get(&self, domain: &str) -> Option<&Response> {
+        self.get_with(domain, &NormalizeOptions::default())
+    }
+
+    // get() for a client built with other NormalizeOptions, so strip_www(true) finds
+    // "example.com" for "www.example.com"
+    pub fn get_with(&self, domain: &str, options: &NormalizeOptions) -> Option<&Response> {
+        self.position(domain)
+            .or_else(|| self.position(&normalize(domain, options).ok()?))
+            .map(|i| &self.response[i])
+    }
+
+    // index over the responses, built once in O(n) and borrowing them
+    pub fn lookup(&self) -> Lookup<'_> {
+        self.lookup_with(&NormalizeOptions::default())
+    }
+
+    pub fn lookup_with(&self, options: &NormalizeOptions) -> Lookup<'_> {
+        let mut index = HashMap::with_capacity(self.response.len());
+        for response in &self.response {
+            let key = match normalize(&response.domain, options) {
+                Ok(domain) if domain != response.domain => Cow::Owned(domain),
+                // already normalized, or an invalid input echoed back by the client
+                _ => Cow::Borrowed(response.domain.as_str()),
+            };
+            index.entry(key).or_insert(response);
+        }
+        Lookup { index, options: options.clone() }
+    }
+
+    pub fn contains(&self, domain: &str) -> bool {
+        self.get(domain).is_some()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Response> {
+        self.response.iter()
+    }
+
+    pub fn responses(&self) -> &[Response] {
+        &self.response
+    }
+
+    pub fn last_updated_str(&self) -> &str {
+        &self.last_updated
+    }
+
+    pub fn len(&self) -> usize {
+        self.response.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.response.is_empty()
+    }
+
+    // keyed by Response::domain, repeated domains keep the first response
+    pub fn into_map(self) -> HashMap<String, Response> {
+        let mut map = HashMap::with_capacity(self.response.len());
+        for response in self.response {
+            map.entry(response.domain.clone()).or_insert(response);
+        }
+        map
+    }
+
+    // a linear scan, response is public and may change between lookups
+    fn position(&self, domain: &str) -> Option<usize> {
+        self.response.iter().position(|x| x.domain == domain)
+    }
+}
+
+// PageRank::lookup, keyed by normalized domain. a normalized domain is found with a single
+// hash lookup and no allocation, other inputs are normalized first.
+#[derive(Debug, Clone)]
+pub struct Lookup<'a> {
+    index: HashMap<Cow<'a, str>, &'a Response>,
+    options: NormalizeOptions,
+}
+
+impl<'a> Lookup<'a> {
+    pub fn get(&self, domain: &str) -> Option<&'a Response> {
+        self.index.get(domain)
+            .or_else(|| self.index.get(normalize(domain, &self.options).ok()?.as_str()))
+            .copied()
+    }
+
+    pub fn contains(&self, domain: &str) -> bool {
+        self.get(domain).is_some()
+    }
+
+    // distinct domains
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+}
+
+impl IntoIterator for PageRank {
+    type Item = Response;
+    type IntoIter = std::vec::IntoIter<Response>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.response.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a PageRank {
+    type Item = &'a Response;
+    type IntoIter = std::slice::Iter<'a, Response>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.response.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rank() -> PageRank {
+        let body = crate::testing::body(vec![
+            crate::testing::found("example.com"),
+            crate::testing::not_found("missing.com"),
+            crate::testing::found("xn--bcher-kva.de"),
+        ]);
+        serde_json::from_str(&body).unwrap()
+    }
+
+    #[test]
+    fn test_get() {
+        let rank = rank();
+        assert_eq!(rank.get("example.com").unwrap().domain, "example.com");
+        assert_eq!(rank.get("https://Example.com/path").unwrap().domain, "example.com");
+        assert_eq!(rank.get("bücher.de").unwrap().domain, "xn--bcher-kva.de");
+        assert!(rank.get("missing.com").unwrap().rank.is_none());
+        assert!(rank.get("other.com").is_none());
+        assert!(!rank.contains("not a domain"));
+    }
+
+    #[test]
+    fn test_lookup() {
+        let mut rank = rank();
+        rank.response.push(rank.response[0].clone());
+        let lookup = rank.lookup();
+        assert_eq!(lookup.len(), 3);
+        assert!(std::ptr::eq(lookup.get("example.com").unwrap(), &rank.response[0]));
+        assert_eq!(lookup.get("https://Example.com/path").unwrap().domain, "example.com");
+        assert_eq!(lookup.get("bücher.de").unwrap().domain, "xn--bcher-kva.de");
+        assert_eq!(lookup.get("missing.com").unwrap().status_code, 404);
+        assert!(!lookup.contains("other.com"));
+        assert!(!lookup.contains("not a domain"));
+    }
+
+    #[test]
+    fn test_lookup_options() {
+        let rank = rank();
+        let options = NormalizeOptions::new().strip_www(true);
+        assert!(rank.get("www.example.com").is_none());
+        assert_eq!(rank.get_with("www.example.com", &options).unwrap().domain, "example.com");
+        assert!(rank.lookup().get("www.example.com").is_none());
+        assert_eq!(rank.lookup_with(&options).get("https://www.example.com/").unwrap().domain, "example.com");
+    }
+
+    #[test]
+    fn test_get_after_modification() {
+        let mut rank = rank();
+        assert!(rank.contains("example.com"));
+        rank.response.remove(0);
+        assert!(rank.get("example.com").is_none());
+        assert_eq!(rank.get("xn--bcher-kva.de").unwrap().domain, "xn--bcher-kva.de");
+
+        rank.response.push(serde_json::from_str(&crate::testing::found("b.com")).unwrap());
+        assert_eq!(rank.get("b.com").unwrap().domain, "b.com");
+        assert!(rank.clone().contains("b.com"));
+    }
+
+    #[test]
+    fn test_iterators() {
+        let rank = rank();
+        assert_eq!(rank.iter().count(), 3);
+        assert_eq!((&rank).into_iter().filter(|x| x.is_found()).count(), 2);
+        assert_eq!(rank.responses().len(), rank.len());
+
+        let map = rank.clone().into_map();
+        assert_eq!(map["missing.com"].status_code, 404);
+        assert_eq!(rank.into_iter().map(|x| x.domain).collect::<Vec<_>>(), ["example.com", "missing.com", "xn--bcher-kva.de"]);
+    }
+}
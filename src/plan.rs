@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+use crate::client::MAX_DOMAINS_PER_REQUEST;
+use crate::normalize::{normalize, NormalizeOptions};
+use crate::{Error, PageRank, Response, Result};
+
+// maps every input position to a unique normalized domain, so each domain is queried once
+// and the responses can be fanned back out to one Response per input
+pub(crate) struct Plan {
+    inputs: Vec<String>,
+    slots: Vec<Option<usize>>,
+    unique: Vec<String>,
+}
+
+impl Plan {
+    pub(crate) fn new<I, T>(inputs: I, options: &NormalizeOptions) -> Self
+    where I: IntoIterator<Item = T>, T: AsRef<str>
+    {
+        let mut index = HashMap::new();
+        let mut plan = Plan { inputs: Vec::new(), slots: Vec::new(), unique: Vec::new() };
+
+        for input in inputs {
+            let slot = normalize(input.as_ref(), options).ok().map(|domain| match index.get(&domain) {
+                Some(i) => *i,
+                None => {
+                    index.insert(domain.clone(), plan.unique.len());
+                    plan.unique.push(domain);
+                    plan.unique.len() - 1
+                }
+            });
+            plan.inputs.push(input.as_ref().to_string());
+            plan.slots.push(slot);
+        }
+        plan
+    }
+
+    pub(crate) fn unique(&self) -> &[String] {
+        &self.unique
+    }
+
+    // the unique domains when they fit in a single request, the api rejects longer queries
+    pub(crate) fn single_request(&self) -> Result<&[String]> {
+        match self.unique.len() {
+            n if n > MAX_DOMAINS_PER_REQUEST => Err(Error::Config(format!(
+                "{} domains in one request, the api takes at most {}, use rank_all", n, MAX_DOMAINS_PER_REQUEST,
+            ))),
+            _ => Ok(&self.unique),
+        }
+    }
+
+    // invalid inputs get a synthesized 400 response, domains the api skipped a 404
+    pub(crate) fn fan_out(&self, rank: PageRank) -> PageRank {
+        let aligned = align(&self.unique, &rank.response);
+
+        let response = self.inputs.iter()
+            .zip(&self.slots)
+            .map(|(input, slot)| match slot {
+                Some(i) => aligned[*i].cloned()
+                    .unwrap_or_else(|| synthesized(404, "Domain not found", &self.unique[*i])),
+                None => synthesized(400, "Invalid domain", input),
+            })
+            .collect();
+
+        PageRank::new(rank.status_code, response, rank.last_updated)
+    }
+}
+
+// the response for every queried domain, matched by domain. when the api answered every
+// domain but renamed some, the unmatched domains take the unclaimed responses in order
+pub(crate) fn align<'a>(domains: &[String], responses: &'a [Response]) -> Vec<Option<&'a Response>> {
+    let by_domain = responses.iter()
+        .enumerate()
+        .map(|(i, r)| (r.domain.to_lowercase(), i))
+        .collect::<HashMap<_, _>>();
+
+    let mut claimed = vec![false; responses.len()];
+    let mut aligned = domains.iter()
+        .map(|domain| {
+            let i = *by_domain.get(domain)?;
+            claimed[i] = true;
+            Some(i)
+        })
+        .collect::<Vec<_>>();
+
+    if responses.len() == domains.len() {
+        let mut unclaimed = (0..responses.len()).filter(|i| !claimed[*i]);
+        for slot in aligned.iter_mut().filter(|x| x.is_none()) {
+            *slot = unclaimed.next();
+        }
+    }
+    aligned.into_iter().map(|x| x.map(|i| &responses[i])).collect()
+}
+
+fn synthesized(status_code: u16, error: &str, domain: &str) -> Response {
+    Response {
+        status_code,
+        error: error.to_string(),
+        page_rank_integer: 0,
+        page_rank_decimal: 0.0,
+        rank: None,
+        domain: domain.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DomainResult;
+
+    fn found(domain: &str) -> Response {
+        Response { status_code: 200, rank: Some(1), ..synthesized(200, "", domain) }
+    }
+
+    #[test]
+    fn test_dedup() {
+        let plan = Plan::new(vec!["a.com", "https://A.com/", "b.com", "bad domain", "a.com"], &NormalizeOptions::default());
+        assert_eq!(plan.unique(), ["a.com", "b.com"]);
+    }
+
+    #[test]
+    fn test_fan_out() {
+        let plan = Plan::new(vec!["a.com", "b.com", "bad domain", "https://a.com", "c.com"], &NormalizeOptions::default());
+        let rank = PageRank::new(200, vec![found("b.com"), found("a.com")], "4th Jan 2024".to_string());
+
+        let rank = plan.fan_out(rank);
+        let domains = rank.response.iter().map(|r| r.domain.as_str()).collect::<Vec<_>>();
+        assert_eq!(domains, ["a.com", "b.com", "bad domain", "a.com", "c.com"]);
+        assert_eq!(rank.response[2].result(), DomainResult::Invalid);
+        assert_eq!(rank.response[3], rank.response[0]);
+        assert_eq!(rank.response[4].result(), DomainResult::NotFound);
+        assert_eq!(rank.last_updated, "4th Jan 2024");
+    }
+
+    #[test]
+    fn test_align_renamed() {
+        let domains = ["a.com".to_string(), "b.com".to_string(), "c.com".to_string()];
+        let responses = [found("b.com"), found("xn--renamed-a.com"), found("C.com")];
+        let aligned = align(&domains, &responses);
+        assert_eq!(aligned[0].unwrap().domain, "xn--renamed-a.com");
+        assert_eq!(aligned[1].unwrap().domain, "b.com");
+        assert_eq!(aligned[2].unwrap().domain, "C.com");
+
+        // not one response per domain, nothing to pair by position
+        let aligned = align(&domains[..2], &responses[..1]);
+        assert_eq!((aligned[0], aligned[1].unwrap().domain.as_str()), (None, "b.com"));
+    }
+}
@@ -0,0 +1,119 @@
+use std::fmt;
+use serde::de::{self, Deserializer, Unexpected, Visitor};
+use serde::Serializer;
+
+// serde helpers for Response::rank, the api sends the global position as a string ("12345")
+// but numbers and null are accepted too. serialized back as a string to keep the wire format.
+
+pub(crate) fn serialize<S>(rank: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error>
+where S: Serializer
+{
+    match rank {
+        Some(rank) => serializer.serialize_str(&rank.to_string()),
+        None => serializer.serialize_none(),
+    }
+}
+
+pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where D: Deserializer<'de>
+{
+    deserializer.deserialize_any(RankVisitor)
+}
+
+struct RankVisitor;
+
+impl<'de> Visitor<'de> for RankVisitor {
+    type Value = Option<u64>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a rank as string, number or null")
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where E: de::Error
+    {
+        Ok(Some(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where E: de::Error
+    {
+        u64::try_from(v).map(Some).map_err(|_| E::invalid_value(Unexpected::Signed(v), &self))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+    where E: de::Error
+    {
+        match v >= 0.0 && v.fract() == 0.0 && v <= u64::MAX as f64 {
+            true => Ok(Some(v as u64)),
+            false => Err(E::invalid_value(Unexpected::Float(v), &self)),
+        }
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where E: de::Error
+    {
+        let v = v.trim();
+        if v.is_empty() {
+            return Ok(None)
+        }
+        v.parse::<u64>().map(Some).map_err(|_| E::invalid_value(Unexpected::Str(v), &self))
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E>
+    where E: de::Error
+    {
+        Ok(None)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where E: de::Error
+    {
+        Ok(None)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where D: Deserializer<'de>
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Response;
+
+    fn parse(rank: &str) -> serde_json::Result<Response> {
+        serde_json::from_str(&format!(
+            r#"{{"status_code":200,"error":"","page_rank_integer":5,"page_rank_decimal":4.5,"rank":{},"domain":"example.com"}}"#,
+            rank
+        ))
+    }
+
+    #[test]
+    fn test_deserialize_rank() {
+        assert_eq!(parse(r#""12345""#).unwrap().rank, Some(12345));
+        assert_eq!(parse("12345").unwrap().rank, Some(12345));
+        assert_eq!(parse("12345.0").unwrap().rank, Some(12345));
+        assert_eq!(parse("null").unwrap().rank, None);
+        assert_eq!(parse(r#""""#).unwrap().rank, None);
+        assert!(parse(r#""n/a""#).is_err());
+        assert!(parse("-1").is_err());
+    }
+
+    #[test]
+    fn test_missing_rank() {
+        let response = serde_json::from_str::<Response>(
+            r#"{"status_code":404,"error":"Domain not found","page_rank_integer":0,"page_rank_decimal":0,"domain":"example.com"}"#
+        ).unwrap();
+        assert_eq!(response.rank, None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let response = parse(r#""12345""#).unwrap();
+        let json = serde_json::to_string(&response).unwrap();
+        assert!(json.contains(r#""rank":"12345""#));
+        assert_eq!(serde_json::from_str::<Response>(&json).unwrap(), response);
+    }
+}
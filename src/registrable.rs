@@ -0,0 +1,132 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use publicsuffix::Psl;
+
+use crate::normalize::{normalize, NormalizeOptions, Normalized};
+use crate::{Error, Result};
+
+// public suffix list used to reduce hosts to their registrable domain (eTLD+1),
+// "blog.shop.example.co.uk" -> "example.co.uk"
+#[derive(Clone)]
+pub enum SuffixList {
+    // snapshot compiled into the psl crate
+    Embedded,
+    // public_suffix_list.dat loaded at runtime, for lists newer than the embedded one
+    Custom(Arc<publicsuffix::List>),
+}
+
+impl fmt::Debug for SuffixList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SuffixList::Embedded => write!(f, "SuffixList::Embedded"),
+            SuffixList::Custom(_) => write!(f, "SuffixList::Custom"),
+        }
+    }
+}
+
+impl PartialEq for SuffixList {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (SuffixList::Embedded, SuffixList::Embedded) => true,
+            (SuffixList::Custom(a), SuffixList::Custom(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for SuffixList {}
+
+impl SuffixList {
+    pub fn embedded() -> Self {
+        SuffixList::Embedded
+    }
+
+    pub fn from_file<P>(path: P) -> Result<Self>
+    where P: AsRef<Path>
+    {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(list: &str) -> Result<Self> {
+        let list = list.parse::<publicsuffix::List>()
+            .map_err(|e| Error::Config(format!("invalid public suffix list: {}", e)))?;
+        Ok(SuffixList::Custom(Arc::new(list)))
+    }
+
+    // expects a normalized ascii host, None when the host is itself a public suffix
+    pub fn registrable_domain(&self, host: &str) -> Option<String> {
+        let domain = match self {
+            SuffixList::Embedded => psl::domain(host.as_bytes())?.as_bytes().to_vec(),
+            SuffixList::Custom(list) => list.domain(host.as_bytes())?.as_bytes().to_vec(),
+        };
+        String::from_utf8(domain).ok()
+    }
+}
+
+// unique registrable domains in first seen order, plus where every input ended up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reduced {
+    pub domains: Vec<String>,
+    pub mapping: Vec<Normalized>,
+}
+
+pub fn reduce<I, T>(hosts: I, list: &SuffixList) -> Reduced
+where I: IntoIterator<Item = T>, T: AsRef<str>
+{
+    let options = NormalizeOptions::new().registrable(list.clone());
+    let mut seen = HashSet::new();
+    let mut domains = Vec::new();
+    let mut mapping = Vec::new();
+
+    for host in hosts {
+        let domain = normalize(host.as_ref(), &options).ok();
+        if let Some(domain) = &domain {
+            if seen.insert(domain.clone()) {
+                domains.push(domain.clone());
+            }
+        }
+        mapping.push(Normalized { input: host.as_ref().to_string(), domain });
+    }
+
+    Reduced { domains, mapping }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    const LIST: &str = "// ===BEGIN ICANN DOMAINS===\ncom\nuk\nco.uk\n// ===END ICANN DOMAINS===\n";
+
+    #[test]
+    fn test_embedded() {
+        let list = SuffixList::embedded();
+        assert_eq!(list.registrable_domain("blog.shop.example.co.uk").as_deref(), Some("example.co.uk"));
+        assert_eq!(list.registrable_domain("www.example.com").as_deref(), Some("example.com"));
+        assert_eq!(list.registrable_domain("co.uk"), None);
+    }
+
+    #[test]
+    fn test_from_file() {
+        let path = testing::temp_path("psl.dat");
+        fs::write(&path, LIST).unwrap();
+        let list = SuffixList::from_file(&path).unwrap();
+        assert_eq!(list.registrable_domain("a.b.example.co.uk").as_deref(), Some("example.co.uk"));
+        assert!(SuffixList::from_file("/nonexistent/psl.dat").is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_reduce() {
+        let reduced = reduce(
+            vec!["https://blog.shop.example.co.uk/post", "shop.example.co.uk", "www.example.com", "co.uk"],
+            &SuffixList::embedded(),
+        );
+        assert_eq!(reduced.domains, vec!["example.co.uk", "example.com"]);
+        assert_eq!(reduced.mapping[1].domain.as_deref(), Some("example.co.uk"));
+        assert_eq!(reduced.mapping[3], Normalized { input: "co.uk".to_string(), domain: None });
+    }
+}
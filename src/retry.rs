@@ -0,0 +1,244 @@
+use std::io;
+use std::time::Duration;
+
+use crate::limit::RateLimiter;
+use crate::{Error, Result};
+
+// exponential backoff for transient failures: timeouts, transport errors and the listed
+// status codes. attempts include the first request, so max_attempts(1) never retries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: bool,
+    retry_statuses: Vec<u16>,
+    respect_retry_after: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(10),
+            jitter: true,
+            retry_statuses: vec![429, 500, 502, 503, 504],
+            respect_retry_after: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn none() -> Self {
+        Self::default().max_attempts(1)
+    }
+
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn retry_statuses(mut self, statuses: Vec<u16>) -> Self {
+        self.retry_statuses = statuses;
+        self
+    }
+
+    // wait as long as a 429 Retry-After asks, giving up if that is longer than max_delay
+    pub fn respect_retry_after(mut self, respect: bool) -> Self {
+        self.respect_retry_after = respect;
+        self
+    }
+
+    pub fn is_retryable(&self, error: &Error) -> bool {
+        match error {
+            Error::Timeout => true,
+            // a rejected certificate fails the same way on every attempt
+            Error::Transport(e) if e.is_connect() => !is_tls(e),
+            Error::Transport(e) => !e.is_builder() && !e.is_redirect(),
+            // what a custom HttpTransport reports for a dropped connection
+            Error::Io(e) => matches!(e.kind(),
+                io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted | io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::BrokenPipe | io::ErrorKind::UnexpectedEof | io::ErrorKind::TimedOut),
+            Error::RateLimited { .. } => self.retry_statuses.contains(&429),
+            Error::Status { code, .. } => self.retry_statuses.contains(code),
+            _ => false,
+        }
+    }
+
+    // delay before the next attempt after `attempt` (1 based) failed with `error`, None to give up
+    pub(crate) fn backoff(&self, attempt: u32, error: &Error) -> Option<Duration> {
+        if attempt >= self.max_attempts || !self.is_retryable(error) {
+            return None
+        }
+
+        if let Error::RateLimited { retry_after: Some(after) } = error {
+            if self.respect_retry_after {
+                return (*after <= self.max_delay).then_some(*after)
+            }
+        }
+
+        let exp = self.base_delay.saturating_mul(1u32 << attempt.saturating_sub(1).min(16));
+        let delay = exp.min(self.max_delay);
+        match self.jitter {
+            // equal jitter, keeps at least half of the computed delay
+            true => Some(delay / 2 + Duration::from_nanos(fastrand::u64(..=(delay / 2).as_nanos() as u64))),
+            false => Some(delay),
+        }
+    }
+}
+
+// the attempt loop of both clients, which only differ in how they sleep and send:
+//
+//     while let Some(wait) = attempts.throttle()? { sleep(wait) }
+//     match send() { Ok(x) => return Ok(x), Err(e) => sleep(attempts.failed(e)?) }
+pub(crate) struct Attempts<'a> {
+    retry: &'a RetryPolicy,
+    limiter: Option<&'a RateLimiter>,
+    attempt: u32,
+}
+
+impl<'a> Attempts<'a> {
+    pub(crate) fn new(retry: &'a RetryPolicy, limiter: Option<&'a RateLimiter>) -> Self {
+        Self { retry, limiter, attempt: 1 }
+    }
+
+    // Some(wait) until the rate limiter lets the next attempt through
+    pub(crate) fn throttle(&self) -> Result<Option<Duration>> {
+        match self.limiter {
+            Some(limiter) => limiter.acquire(),
+            None => Ok(None),
+        }
+    }
+
+    // the delay before the next attempt, or the error when the policy gives up
+    pub(crate) fn failed(&mut self, error: Error) -> Result<Duration> {
+        let delay = self.retry.backoff(self.attempt, &error).ok_or(error)?;
+        self.attempt += 1;
+        Ok(delay)
+    }
+}
+
+// rustls errors reach reqwest wrapped in (nested) io errors, whose source() skips the wrapped error itself
+fn is_tls(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(error);
+    while let Some(e) = source {
+        if e.is::<rustls::Error>() {
+            return true
+        }
+        source = match e.downcast_ref::<io::Error>().and_then(|x| x.get_ref()) {
+            Some(inner) => Some(inner),
+            None => e.source(),
+        };
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use super::*;
+    use crate::testing::{self, aw, MockServer, Reply};
+    use crate::PageRankClient;
+
+    fn failing(times: usize, status: u16) -> MockServer {
+        let count = AtomicUsize::new(0);
+        MockServer::start(move |request| match count.fetch_add(1, Ordering::SeqCst) < times {
+            true => Reply::status(status, "unavailable".to_string()).header("retry-after", "0"),
+            false => testing::echo(request),
+        })
+    }
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy::new().base_delay(Duration::from_millis(1)).jitter(false)
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = policy().max_attempts(10).max_delay(Duration::from_millis(5));
+        let error = Error::Status { code: 503, body: String::new() };
+        assert_eq!(policy.backoff(1, &error), Some(Duration::from_millis(1)));
+        assert_eq!(policy.backoff(3, &error), Some(Duration::from_millis(4)));
+        assert_eq!(policy.backoff(4, &error), Some(Duration::from_millis(5)));
+        assert_eq!(policy.backoff(10, &error), None);
+        assert_eq!(policy.backoff(1, &Error::InvalidKey), None);
+        assert!(policy.backoff(1, &io::Error::from(io::ErrorKind::ConnectionReset).into()).is_some());
+        assert_eq!(policy.backoff(1, &io::Error::from(io::ErrorKind::NotFound).into()), None);
+
+        let limited = Error::RateLimited { retry_after: Some(Duration::from_secs(60)) };
+        assert_eq!(policy.backoff(1, &limited), None);
+        assert_eq!(policy.clone().max_delay(Duration::from_secs(60)).backoff(1, &limited), Some(Duration::from_secs(60)));
+
+        let jittered = RetryPolicy::new().base_delay(Duration::from_millis(100));
+        let delay = jittered.backoff(1, &error).unwrap();
+        assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_attempts() {
+        let policy = policy().max_attempts(3);
+        let error = || Error::Status { code: 503, body: String::new() };
+        let mut attempts = Attempts::new(&policy, None);
+        assert_eq!(attempts.throttle().unwrap(), None);
+        assert_eq!(attempts.failed(error()).unwrap(), Duration::from_millis(1));
+        assert_eq!(attempts.failed(error()).unwrap(), Duration::from_millis(2));
+        assert!(matches!(attempts.failed(error()), Err(Error::Status { code: 503, .. })));
+
+        let limiter = RateLimiter::new(crate::RateLimit::new().per_minute(1));
+        let attempts = Attempts::new(&policy, Some(&limiter));
+        assert_eq!(attempts.throttle().unwrap(), None);
+        assert!(attempts.throttle().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_retries_until_success() {
+        let server = failing(2, 503);
+        let client = PageRankClient::builder("key").base_url(server.url()).retry(policy()).build().unwrap();
+        let rank = aw!(client.rank(vec!["example.com"])).unwrap();
+        assert_eq!(rank.response[0].domain, "example.com");
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn test_gives_up_after_max_attempts() {
+        let server = failing(5, 503);
+        let client = PageRankClient::builder("key").base_url(server.url()).retry(policy().max_attempts(2)).build().unwrap();
+        assert!(matches!(aw!(client.rank(vec!["example.com"])), Err(Error::Status { code: 503, .. })));
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn test_honors_retry_after() {
+        let server = failing(1, 429);
+        let client = PageRankClient::builder("key").base_url(server.url()).retry(policy()).build().unwrap();
+        assert!(aw!(client.rank(vec!["example.com"])).is_ok());
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn test_no_retry_by_default() {
+        let server = failing(1, 503);
+        let client = PageRankClient::builder("key").base_url(server.url()).build().unwrap();
+        assert!(aw!(client.rank(vec!["example.com"])).is_err());
+        assert_eq!(server.requests().len(), 1);
+    }
+}
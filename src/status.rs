@@ -0,0 +1,111 @@
+use crate::{PageRank, PageRankFirst, Response};
+
+// per domain outcome, the api reports it through Response::status_code and Response::error
+// and leaves the rank fields zeroed when the lookup failed
+#[derive(Debug, Clone, PartialEq)]
+pub enum DomainResult {
+    Found { rank: Option<u64>, integer: u32, decimal: f32 },
+    NotFound,
+    Invalid,
+    Other { code: u16, msg: String },
+}
+
+impl From<&Response> for DomainResult {
+    fn from(response: &Response) -> Self {
+        match response.status_code {
+            200 => DomainResult::Found {
+                rank: response.rank,
+                integer: response.page_rank_integer,
+                decimal: response.page_rank_decimal,
+            },
+            404 => DomainResult::NotFound,
+            400 | 422 => DomainResult::Invalid,
+            code => DomainResult::Other { code, msg: response.error.clone() },
+        }
+    }
+}
+
+impl From<Response> for DomainResult {
+    fn from(response: Response) -> Self {
+        DomainResult::from(&response)
+    }
+}
+
+impl DomainResult {
+    pub fn is_found(&self) -> bool {
+        matches!(self, DomainResult::Found { .. })
+    }
+}
+
+impl Response {
+    pub fn result(&self) -> DomainResult {
+        DomainResult::from(self)
+    }
+
+    pub fn is_found(&self) -> bool {
+        self.status_code == 200
+    }
+}
+
+impl PageRankFirst {
+    pub fn result(&self) -> DomainResult {
+        self.response.result()
+    }
+
+    pub fn is_found(&self) -> bool {
+        self.response.is_found()
+    }
+}
+
+impl PageRank {
+    pub fn found(&self) -> impl Iterator<Item = &Response> {
+        self.response.iter().filter(|x| x.is_found())
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &Response> {
+        self.response.iter().filter(|x| !x.is_found())
+    }
+
+    // (found, failed), both in response order
+    pub fn partition(&self) -> (Vec<&Response>, Vec<&Response>) {
+        self.response.iter().partition(|x| x.is_found())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(status_code: u16, error: &str) -> Response {
+        Response {
+            status_code,
+            error: error.to_string(),
+            page_rank_integer: if status_code == 200 { 5 } else { 0 },
+            page_rank_decimal: if status_code == 200 { 4.5 } else { 0.0 },
+            rank: if status_code == 200 { Some(1000) } else { None },
+            domain: "example.com".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_domain_result() {
+        assert_eq!(response(200, "").result(), DomainResult::Found { rank: Some(1000), integer: 5, decimal: 4.5 });
+        assert_eq!(response(404, "Domain not found").result(), DomainResult::NotFound);
+        assert_eq!(response(400, "Invalid domain").result(), DomainResult::Invalid);
+        assert_eq!(response(500, "boom").result(), DomainResult::Other { code: 500, msg: "boom".to_string() });
+    }
+
+    #[test]
+    fn test_partition() {
+        let rank = PageRank::new(
+            200,
+            vec![response(200, ""), response(404, "Domain not found"), response(200, "")],
+            "4th Jan 2024".to_string(),
+        );
+        let (found, failed) = rank.partition();
+        assert_eq!(found.len(), 2);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(rank.found().count(), 2);
+        assert_eq!(rank.failed().next().unwrap().result(), DomainResult::NotFound);
+    }
+}
@@ -0,0 +1,238 @@
+// in-process stand-in for the open pagerank api, for this crate's tests and for tests of code
+// built on it (enable the `testing` feature):
+//
+//     let server = MockServer::start(testing::known(&[("example.com", 1234, 4.5)]));
+//     let client = PageRankClient::builder("key").base_url(server.url()).build()?;
+//
+// handlers turn every request into a Reply, the helpers below build realistic payloads.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use reqwest::Url;
+
+pub const LAST_UPDATED: &str = "4th Jan 2024";
+
+// plain http server on 127.0.0.1, each connection gets one reply from `handler`.
+// runs until the process exits.
+pub struct MockServer {
+    url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub path: String,
+    pub domains: Vec<String>,
+    // lowercase names
+    pub headers: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Reply {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+    delay: Option<Duration>,
+}
+
+impl MockServer {
+    pub fn start<F>(handler: F) -> Self
+    where F: Fn(&Request) -> Reply + Send + Sync + 'static
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler = Arc::new(handler);
+
+        let log = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let handler = handler.clone();
+                let log = log.clone();
+                thread::spawn(move || handle(stream, &*handler, &log));
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    // pass to PageRankClientBuilder::base_url
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    // every request so far, in arrival order
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+        self.headers.iter().find(|(k, _)| *k == name).map(|(_, v)| v.as_str())
+    }
+}
+
+fn handle<F>(mut stream: TcpStream, handler: &F, log: &Mutex<Vec<Request>>)
+where F: Fn(&Request) -> Reply
+{
+    let mut raw = Vec::new();
+    let mut buf = [0u8; 4096];
+    while !raw.windows(4).any(|w| w == b"\r\n\r\n") {
+        match stream.read(&mut buf) {
+            Ok(0) | Err(_) => return,
+            Ok(n) => raw.extend_from_slice(&buf[..n]),
+        }
+    }
+
+    let raw = String::from_utf8_lossy(&raw);
+    let mut lines = raw.lines();
+    let target = lines.next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .unwrap_or("/");
+    let url = Url::parse(&format!("http://localhost{}", target)).unwrap();
+    let request = Request {
+        path: url.path().to_string(),
+        domains: url.query_pairs()
+            .filter(|(k, _)| k == "domains[]")
+            .map(|(_, v)| v.into_owned())
+            .collect(),
+        headers: lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(k, v)| (k.trim().to_lowercase(), v.trim().to_string()))
+            .collect(),
+    };
+    log.lock().unwrap().push(request.clone());
+
+    let reply = handler(&request);
+    if let Some(delay) = reply.delay {
+        thread::sleep(delay);
+    }
+
+    let mut response = format!("HTTP/1.1 {} Mock\r\ncontent-length: {}\r\nconnection: close\r\n", reply.status, reply.body.len());
+    for (k, v) in &reply.headers {
+        response.push_str(&format!("{}: {}\r\n", k, v));
+    }
+    response.push_str("\r\n");
+    response.push_str(&reply.body);
+    let _ = stream.write_all(response.as_bytes());
+}
+
+impl Reply {
+    pub fn json(body: String) -> Self {
+        Self::status(200, body).header("content-type", "application/json")
+    }
+
+    pub fn status(status: u16, body: String) -> Self {
+        Self { status, headers: Vec::new(), body, delay: None }
+    }
+
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        self.headers.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    // sleeps before answering, for timeouts
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+}
+
+// one entry of the response array
+pub fn ranked(domain: &str, rank: u64, decimal: f32) -> String {
+    format!(
+        r#"{{"status_code":200,"error":"","page_rank_integer":{},"page_rank_decimal":{},"rank":"{}","domain":"{}"}}"#,
+        decimal.round() as u32, decimal, rank, domain,
+    )
+}
+
+pub fn found(domain: &str) -> String {
+    ranked(domain, 1000, 4.5)
+}
+
+pub fn not_found(domain: &str) -> String {
+    format!(r#"{{"status_code":404,"error":"Domain not found","page_rank_integer":0,"page_rank_decimal":0,"rank":null,"domain":"{}"}}"#, domain)
+}
+
+// full payload around response entries
+pub fn body(responses: Vec<String>) -> String {
+    format!(r#"{{"status_code":200,"response":[{}],"last_updated":"{}"}}"#, responses.join(","), LAST_UPDATED)
+}
+
+// answers every queried domain as found
+pub fn echo(request: &Request) -> Reply {
+    Reply::json(body(request.domains.iter().map(|d| found(d)).collect()))
+}
+
+// a small dataset of (domain, rank, decimal), anything else is not found
+pub fn known(domains: &[(&str, u64, f32)]) -> impl Fn(&Request) -> Reply + Send + Sync + 'static {
+    let known = domains.iter()
+        .map(|(domain, rank, decimal)| (domain.to_string(), (*rank, *decimal)))
+        .collect::<HashMap<_, _>>();
+
+    move |request| Reply::json(body(request.domains.iter()
+        .map(|d| match known.get(d) {
+            Some((rank, decimal)) => ranked(d, *rank, *decimal),
+            None => not_found(d),
+        })
+        .collect()))
+}
+
+// what the api answers for a missing or wrong API-OPR header
+pub fn invalid_key() -> Reply {
+    Reply::status(401, r#"{"status_code":401,"error":"Invalid API key"}"#.to_string())
+        .header("content-type", "application/json")
+}
+
+pub fn rate_limited(retry_after: u64) -> Reply {
+    Reply::status(429, r#"{"status_code":429,"error":"Too Many Requests"}"#.to_string())
+        .header("retry-after", &retry_after.to_string())
+}
+
+// a 200 with a payload cut off mid way
+pub fn malformed() -> Reply {
+    Reply::json(r#"{"status_code":200,"response":[{"status_code":200,"dom"#.to_string())
+}
+
+// `handler` after sleeping `delay`
+pub fn slow<F>(delay: Duration, handler: F) -> impl Fn(&Request) -> Reply + Send + Sync + 'static
+where F: Fn(&Request) -> Reply + Send + Sync + 'static
+{
+    move |request| handler(request).delay(delay)
+}
+
+// helpers for this crate's own tests
+
+#[cfg(test)]
+macro_rules! aw {
+    ($e:expr) => {
+        tokio_test::block_on($e)
+    };
+}
+#[cfg(test)]
+pub(crate) use aw;
+
+// a found entry from the LAST_UPDATED dataset
+#[cfg(test)]
+pub(crate) fn cache_entry(domain: &str, fetched_at: u64) -> crate::CacheEntry {
+    crate::CacheEntry {
+        domain: domain.to_string(),
+        response: serde_json::from_str(&found(domain)).unwrap(),
+        fetched_at,
+        last_updated: LAST_UPDATED.to_string(),
+    }
+}
+
+// a fresh path in the temp dir, unique per process so concurrent test runs don't collide
+#[cfg(test)]
+pub(crate) fn temp_path(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("pagerank-test-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_file(&path);
+    path
+}
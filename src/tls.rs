@@ -0,0 +1,280 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use reqwest::ClientBuilder;
+use ring::digest::{digest, SHA256};
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName};
+
+use crate::{Error, Result};
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TlsSettings {
+    pub(crate) accept_invalid_certs: bool,
+    pub(crate) root_certificates: Vec<PathBuf>,
+    pub(crate) pins: Vec<String>,
+}
+
+// resolved settings, applied to either the async or the blocking reqwest builder
+pub(crate) enum Tls {
+    Builtin { accept_invalid_certs: bool, roots: Vec<reqwest::Certificate> },
+    Pinned(ClientConfig),
+}
+
+impl TlsSettings {
+    pub(crate) fn resolve(&self) -> Result<Tls> {
+        let mut roots = Vec::new();
+        for path in &self.root_certificates {
+            roots.extend(load_pem(path)?);
+        }
+
+        // reqwest handles plain verification itself, pinning needs our own rustls verifier
+        if self.pins.is_empty() {
+            let roots = roots.iter()
+                .map(|der| reqwest::Certificate::from_der(der).map_err(|e| Error::Tls(e.to_string())))
+                .collect::<Result<Vec<_>>>()?;
+            return Ok(Tls::Builtin { accept_invalid_certs: self.accept_invalid_certs, roots })
+        }
+
+        let pins = self.pins.iter()
+            .map(|pin| parse_fingerprint(pin))
+            .collect::<Result<Vec<_>>>()?;
+
+        let inner = if self.accept_invalid_certs {
+            None
+        } else {
+            let mut store = RootCertStore::empty();
+            store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+                OwnedTrustAnchor::from_subject_spki_name_constraints(ta.subject, ta.spki, ta.name_constraints)
+            }));
+            for der in roots {
+                store.add(&Certificate(der)).map_err(|e| Error::Tls(e.to_string()))?;
+            }
+            Some(WebPkiVerifier::new(store, None))
+        };
+
+        let config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(Arc::new(PinnedVerifier { inner, pins }))
+            .with_no_client_auth();
+
+        Ok(Tls::Pinned(config))
+    }
+}
+
+impl Tls {
+    pub(crate) fn apply(self, builder: ClientBuilder) -> ClientBuilder {
+        match self {
+            Tls::Builtin { accept_invalid_certs, roots } => roots.into_iter().fold(
+                builder.danger_accept_invalid_certs(accept_invalid_certs),
+                |builder, cert| builder.add_root_certificate(cert),
+            ),
+            Tls::Pinned(config) => builder.use_preconfigured_tls(config),
+        }
+    }
+
+    #[cfg(feature = "blocking")]
+    pub(crate) fn apply_blocking(self, builder: reqwest::blocking::ClientBuilder) -> reqwest::blocking::ClientBuilder {
+        match self {
+            Tls::Builtin { accept_invalid_certs, roots } => roots.into_iter().fold(
+                builder.danger_accept_invalid_certs(accept_invalid_certs),
+                |builder, cert| builder.add_root_certificate(cert),
+            ),
+            Tls::Pinned(config) => builder.use_preconfigured_tls(config),
+        }
+    }
+}
+
+// accepts the chain only if the leaf certificate matches one of the pinned sha256 fingerprints.
+// without an inner verifier (insecure mode) the pin is the only check.
+struct PinnedVerifier {
+    inner: Option<WebPkiVerifier>,
+    pins: Vec<[u8; 32]>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Some(inner) = &self.inner {
+            inner.verify_server_cert(end_entity, intermediates, server_name, scts, ocsp_response, now)?;
+        }
+
+        let fingerprint = digest(&SHA256, &end_entity.0);
+        match self.pins.iter().any(|pin| pin[..] == *fingerprint.as_ref()) {
+            true => Ok(ServerCertVerified::assertion()),
+            false => Err(rustls::Error::General("certificate does not match any pinned fingerprint".to_string())),
+        }
+    }
+}
+
+fn load_pem(path: &Path) -> Result<Vec<Vec<u8>>> {
+    let file = File::open(path).map_err(|e| Error::Tls(format!("failed to open {}: {}", path.display(), e)))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .map_err(|e| Error::Tls(format!("failed to parse {}: {}", path.display(), e)))?;
+
+    if certs.is_empty() {
+        return Err(Error::Tls(format!("no certificates found in {}", path.display())))
+    }
+    Ok(certs)
+}
+
+// hex encoded sha256 of the DER certificate, colons allowed (openssl x509 -fingerprint -sha256)
+fn parse_fingerprint(s: &str) -> Result<[u8; 32]> {
+    let invalid = || Error::Config(format!("invalid sha256 fingerprint: {}", s));
+
+    let hex = s.chars().filter(|c| *c != ':').collect::<String>();
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err(invalid())
+    }
+
+    let mut fingerprint = [0u8; 32];
+    for (i, byte) in fingerprint.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(fingerprint)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use super::*;
+    use crate::testing::{self, aw};
+    use crate::{PageRankClient, RetryPolicy};
+
+    const BODY: &str = r#"{"status_code":200,"response":[{"status_code":200,"error":"","page_rank_integer":5,"page_rank_decimal":5.0,"rank":"100","domain":"example.com"}],"last_updated":"4th Jan 2024"}"#;
+
+    struct Server {
+        url: String,
+        pem: PathBuf,
+        fingerprint: String,
+    }
+
+    // tls stand-in for openpagerank.com using a freshly generated self-signed certificate
+    fn serve() -> Server {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let der = cert.serialize_der().unwrap();
+        let config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(vec![Certificate(der.clone())], rustls::PrivateKey(cert.serialize_private_key_der()))
+            .unwrap();
+        let config = Arc::new(config);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let config = config.clone();
+                thread::spawn(move || {
+                    let conn = rustls::ServerConnection::new(config).unwrap();
+                    let mut tls = rustls::StreamOwned::new(conn, stream);
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 4096];
+                    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                        match tls.read(&mut buf) {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                        BODY.len(), BODY
+                    );
+                    let _ = tls.write_all(response.as_bytes());
+                    tls.conn.send_close_notify();
+                    let _ = tls.flush();
+                });
+            }
+        });
+
+        let pem = testing::temp_path(&format!("{}.pem", port));
+        std::fs::write(&pem, cert.serialize_pem().unwrap()).unwrap();
+
+        Server {
+            url: format!("https://localhost:{}", port),
+            pem,
+            fingerprint: digest(&SHA256, &der).as_ref().iter().map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+
+    // the pem goes with the server at the end of each test, failed ones included
+    impl Drop for Server {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.pem);
+        }
+    }
+
+    #[test]
+    fn test_rejects_self_signed_by_default() {
+        let server = serve();
+        let client = PageRankClient::builder("key").base_url(&server.url).build().unwrap();
+        let error = aw!(client.rank(vec!["example.com"])).unwrap_err();
+        assert!(!RetryPolicy::new().is_retryable(&error));
+    }
+
+    #[test]
+    fn test_insecure_opt_in() {
+        let server = serve();
+        let client = PageRankClient::builder("key")
+            .base_url(&server.url)
+            .danger_accept_invalid_certs(true)
+            .build()
+            .unwrap();
+        let rank = aw!(client.rank(vec!["example.com"])).unwrap();
+        assert_eq!(rank.response[0].domain, "example.com");
+    }
+
+    #[test]
+    fn test_custom_root_certificate() {
+        let server = serve();
+        let client = PageRankClient::builder("key")
+            .base_url(&server.url)
+            .add_root_certificate_pem(&server.pem)
+            .build()
+            .unwrap();
+        assert!(aw!(client.rank(vec!["example.com"])).is_ok());
+
+        let pem = server.pem.clone();
+        drop(server);
+        assert!(!pem.exists());
+    }
+
+    #[test]
+    fn test_certificate_pinning() {
+        let server = serve();
+        let pinned = PageRankClient::builder("key")
+            .base_url(&server.url)
+            .add_root_certificate_pem(&server.pem)
+            .pin_sha256(&server.fingerprint)
+            .build()
+            .unwrap();
+        assert!(aw!(pinned.rank(vec!["example.com"])).is_ok());
+
+        let mismatched = PageRankClient::builder("key")
+            .base_url(&server.url)
+            .add_root_certificate_pem(&server.pem)
+            .pin_sha256(&"00".repeat(32))
+            .build()
+            .unwrap();
+        let error = aw!(mismatched.rank(vec!["example.com"])).unwrap_err();
+        assert!(!RetryPolicy::new().is_retryable(&error));
+    }
+
+    #[test]
+    fn test_parse_fingerprint() {
+        assert_eq!(parse_fingerprint(&format!("{}ab", "ab:".repeat(31))).unwrap(), [0xab; 32]);
+        assert!(parse_fingerprint("abcd").is_err());
+        assert!(parse_fingerprint(&"zz".repeat(32)).is_err());
+    }
+}
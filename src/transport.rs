@@ -0,0 +1,192 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use futures::future::BoxFuture;
+use reqwest::header::HeaderMap;
+use reqwest::{Client, Url};
+
+use crate::Result;
+
+// a GET of the getPageRank endpoint, the domains are already in the url query
+// and the API-OPR key in the headers
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub url: Url,
+    pub headers: HeaderMap,
+    pub timeout: Duration,
+}
+
+impl HttpRequest {
+    // domains[] query values, in order
+    pub fn domains(&self) -> Vec<String> {
+        self.url.query_pairs()
+            .filter(|(k, _)| k == "domains[]")
+            .map(|(_, v)| v.into_owned())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+// sends the api requests of a PageRankClient, implement it to instrument requests, fake the
+// api in unit tests or use another http stack. report timeouts as Error::Timeout and other
+// failures as Error::Io, the RetryPolicy retries the connection kinds (ConnectionReset,
+// ConnectionRefused, BrokenPipe, UnexpectedEof, ...). status codes and decoding are left to the client.
+pub trait HttpTransport: Send + Sync + fmt::Debug {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse>>;
+}
+
+impl<T> HttpTransport for Arc<T>
+where T: HttpTransport + ?Sized
+{
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse>> {
+        (**self).send(request)
+    }
+}
+
+// the default transport, built from the tls, proxy and user agent settings of the builder
+#[derive(Debug, Clone)]
+pub struct ReqwestTransport {
+    client: Client,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+impl HttpTransport for ReqwestTransport {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse>> {
+        Box::pin(async move {
+            let response = self.client.get(request.url)
+                .headers(request.headers)
+                .timeout(request.timeout)
+                .send()
+                .await?;
+
+            let status = response.status().as_u16();
+            let headers = response.headers().clone();
+            let body = response.text().await?;
+            Ok(HttpResponse { status, headers, body })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::testing::{self, aw, MockServer};
+    use crate::{Error, PageRankClient, RetryPolicy};
+
+    // answers from memory and keeps every request
+    #[derive(Debug, Default)]
+    struct Fake {
+        requests: Mutex<Vec<HttpRequest>>,
+    }
+
+    impl HttpTransport for Fake {
+        fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse>> {
+            let body = testing::body(request.domains().iter().map(|x| testing::not_found(x)).collect());
+            self.requests.lock().unwrap().push(request);
+            Box::pin(async move { Ok(HttpResponse { status: 200, headers: HeaderMap::new(), body }) })
+        }
+    }
+
+    // counts what goes through the default transport
+    #[derive(Debug)]
+    struct Counting {
+        inner: ReqwestTransport,
+        count: Mutex<usize>,
+    }
+
+    impl HttpTransport for Counting {
+        fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse>> {
+            *self.count.lock().unwrap() += 1;
+            self.inner.send(request)
+        }
+    }
+
+    // drops the connection `failures` times before passing requests on
+    #[derive(Debug)]
+    struct Flaky {
+        inner: Arc<Fake>,
+        failures: Mutex<usize>,
+    }
+
+    impl HttpTransport for Flaky {
+        fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse>> {
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Box::pin(async { Err(std::io::Error::from(std::io::ErrorKind::ConnectionReset).into()) })
+            }
+            self.inner.send(request)
+        }
+    }
+
+    #[test]
+    fn test_fake_transport() {
+        let fake = Arc::new(Fake::default());
+        let client = PageRankClient::builder("key")
+            .base_url("https://openpagerank.com/api/v1.0")
+            .transport(fake.clone())
+            .build()
+            .unwrap();
+
+        let rank = aw!(client.rank(vec!["a.com", "https://b.com/"])).unwrap();
+        assert_eq!(rank.response[1].status_code, 404);
+
+        let requests = fake.requests.lock().unwrap();
+        assert_eq!(requests[0].domains(), ["a.com", "b.com"]);
+        assert_eq!(requests[0].headers["api-opr"], "key");
+        assert_eq!(requests[0].url.path(), "/api/v1.0/getPageRank");
+    }
+
+    #[test]
+    fn test_io_errors_retried() {
+        let fake = Arc::new(Fake::default());
+        let flaky = Flaky { inner: fake.clone(), failures: Mutex::new(1) };
+        let client = PageRankClient::builder("key")
+            .base_url("https://openpagerank.com/api/v1.0")
+            .transport(flaky)
+            .retry(RetryPolicy::new().base_delay(Duration::from_millis(1)))
+            .build()
+            .unwrap();
+
+        assert!(aw!(client.rank(vec!["a.com"])).is_ok());
+        assert_eq!(fake.requests.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_wrapped_transport() {
+        let server = MockServer::start(testing::echo);
+        let counting = Arc::new(Counting { inner: ReqwestTransport::new(Client::new()), count: Mutex::new(0) });
+        let client = PageRankClient::builder("key")
+            .base_url(server.url())
+            .transport(counting.clone())
+            .build()
+            .unwrap();
+
+        aw!(client.rank_all((0..150).map(|i| format!("domain{}.com", i)))).unwrap();
+        assert_eq!(*counting.count.lock().unwrap(), 2);
+        assert_eq!(server.requests()[0].header("api-opr"), Some("key"));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let server = MockServer::start(testing::slow(Duration::from_millis(500), testing::echo));
+        let request = HttpRequest {
+            url: server.url().parse().unwrap(),
+            headers: HeaderMap::new(),
+            timeout: Duration::from_millis(50),
+        };
+        assert!(matches!(aw!(ReqwestTransport::new(Client::new()).send(request)), Err(Error::Timeout)));
+    }
+}